@@ -0,0 +1,378 @@
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{
+        self,
+        Seek,
+        SeekFrom,
+        prelude::*,
+    },
+    path::{Path, PathBuf},
+};
+
+use super::{
+    Entry,
+    archive::{
+        Archive,
+        SeekableArchive,
+        SeekableArchiveBuilder,
+    },
+    error::{self, Error},
+    format::Format,
+    loose,
+    applesingle::{
+        self,
+        APPLEDOUBLE_MAGIC,
+        EntrySource,
+        EntryType,
+        Handler,
+        Violation,
+    },
+};
+
+/// Parses an AppleDouble header file. The data fork lives in a separate file
+/// so only the resource fork and other entries will be sent to the handler.
+pub fn parse<R: Read, H: Handler>(
+    header: R,
+    handler: &mut H,
+) -> error::Result<Archive> {
+    applesingle::parse_archive(header, handler, APPLEDOUBLE_MAGIC, Format::AppleDouble)
+}
+
+/// Parses the AppleDouble header file which accompanies a folder.
+pub fn parse_folder<R: Read, H: Handler>(
+    header: R,
+    handler: &mut H,
+) -> error::Result<Archive> {
+    parse(header, handler)?.into_folder()
+}
+
+/// Checks an AppleDouble header file against the specification. See
+/// [`applesingle::validate`].
+pub fn validate<R: Read>(header: R) -> error::Result<Vec<Violation>> {
+    applesingle::validate_archive(header, APPLEDOUBLE_MAGIC)
+}
+
+/// Parses an AppleDouble header file along with its companion data file,
+/// presenting them as a single archive.
+pub fn parse_seekable<H: Read + Seek, D: Read + Seek>(
+    header: H,
+    data: D,
+) -> error::Result<SeekableArchive<AppleDoubleFile<H, D>>> {
+    let file = AppleDoubleFile::new(header, data)?;
+    parse_file(SeekableArchive::builder(file))
+}
+
+/// Parses the header file at the start of the builder's file, keeping the
+/// metadata the builder already has unless the header file replaces it.
+fn parse_file<H: Read + Seek, D: Read + Seek>(
+    mut builder: SeekableArchiveBuilder<AppleDoubleFile<H, D>>,
+) -> error::Result<SeekableArchive<AppleDoubleFile<H, D>>> {
+    let file = builder.file();
+    let data_fork = Entry {
+        id: EntryType::DataFork.into(),
+        offset: file.header_len,
+        len: file.data_len,
+    };
+    applesingle::parse_header_entries(&mut builder, Format::AppleDouble, data_fork.offset)?;
+    builder.data_fork(data_fork);
+    builder.table_entry(data_fork);
+    builder.build()
+        .ok_or(Error::Incomplete)
+}
+
+/// Where an AppleDouble header file is kept relative to the file or folder
+/// it describes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// A `._` file next to it, as written by macOS on foreign file systems
+    /// and by Samba.
+    DotUnderscore,
+    /// A file of the same name in a `.AppleDouble` folder next to it, as
+    /// written by netatalk before 3.0. A folder's own header is kept in its
+    /// `.AppleDouble` folder as `.Parent`.
+    Netatalk,
+}
+
+impl Layout {
+    /// Every layout, in the order they are searched.
+    pub const ALL: [Self; 2] = [Self::DotUnderscore, Self::Netatalk];
+
+    /// Finds the path of the header file for the data file at `path`.
+    pub fn header_path<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        match self {
+            Self::DotUnderscore => {
+                let mut name = OsString::from("._");
+                name.push(path.file_name()?);
+                Some(path.with_file_name(name))
+            },
+            Self::Netatalk => {
+                let name = path.file_name()?;
+                Some(path.with_file_name(".AppleDouble").join(name))
+            },
+        }
+    }
+    /// Finds the path of the header file for the folder at `path`.
+    pub fn folder_header_path<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let path = path.as_ref();
+        match self {
+            Self::DotUnderscore => self.header_path(path),
+            Self::Netatalk => Some(path.join(".AppleDouble").join(".Parent")),
+        }
+    }
+    fn header_path_for<P: AsRef<Path>>(&self, path: P, folder: bool) -> Option<PathBuf> {
+        match folder {
+            true => self.folder_header_path(path),
+            false => self.header_path(path),
+        }
+    }
+}
+
+/// Finds the header file which accompanies the file or folder at `path` in
+/// whichever layout it was written.
+pub fn find_header<P: AsRef<Path>>(path: P) -> Option<(Layout, PathBuf)> {
+    let path = path.as_ref();
+    let folder = path.is_dir();
+    Layout::ALL.into_iter()
+        .filter_map(|layout| Some((layout, layout.header_path_for(path, folder)?)))
+        .find(|(_, header_path)| header_path.is_file())
+}
+
+fn open_header<P: AsRef<Path>>(path: P) -> io::Result<File> {
+    let (_, header_path) = find_header(path)
+        .ok_or(io::ErrorKind::NotFound)?;
+    File::open(header_path)
+}
+
+/// Opens the data file at `path` along with its header file, which may be in
+/// any [`Layout`]. Header files written by macOS hold neither a name nor
+/// dates, so unless the header file has its own, they are taken from the
+/// data file.
+pub fn open<P: AsRef<Path>>(
+    path: P,
+) -> error::Result<SeekableArchive<AppleDoubleFile<File, File>>> {
+    let path = path.as_ref();
+    let header = open_header(path)?;
+    let data = File::open(path)?;
+    let metadata = data.metadata()?;
+    let mut builder = SeekableArchive::builder(AppleDoubleFile::new(header, data)?);
+    loose::describe(builder.archive(), path, &metadata);
+    parse_file(builder)
+}
+
+/// Opens the header file which accompanies the folder at `path`, which may
+/// be in any [`Layout`].
+pub fn open_folder<P: AsRef<Path>>(
+    path: P,
+) -> error::Result<SeekableArchive<File>> {
+    let header = open_header(path)?;
+    applesingle::parse_seekable_archive(header, APPLEDOUBLE_MAGIC, Format::AppleDouble)?
+        .into_folder()
+}
+
+/// Finds the path of the `._` header file which accompanies the data file at
+/// `path`, as written by macOS on foreign file systems.
+pub fn header_path<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    Layout::DotUnderscore.header_path(path)
+}
+
+/// Moves the header file of the file or folder at `path` into `layout`,
+/// returning whether there was one to move.
+pub fn relocate_header<P: AsRef<Path>>(path: P, layout: Layout) -> io::Result<bool> {
+    let path = path.as_ref();
+    let (current, header_path) = match find_header(path) {
+        Some(found) => found,
+        None => return Ok(false),
+    };
+    if current != layout {
+        let new_path = layout.header_path_for(path, path.is_dir())
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        create_parent(&new_path)?;
+        fs::rename(header_path, new_path)?;
+    }
+    Ok(true)
+}
+
+/// Creates the folder that will hold a header file, if needed.
+fn create_parent(header_path: &Path) -> io::Result<()> {
+    match header_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+/// An AppleDouble header file and its data file, viewed as one seekable
+/// stream in which the data file immediately follows the header file.
+pub struct AppleDoubleFile<H, D> {
+    header: H,
+    data: D,
+    header_len: u64,
+    data_len: u64,
+    position: u64,
+}
+
+impl <H: Seek, D: Seek> AppleDoubleFile<H, D> {
+    pub(crate) fn new(mut header: H, mut data: D) -> io::Result<Self> {
+        let header_len = header.seek(SeekFrom::End(0))?;
+        let data_len = data.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            header,
+            data,
+            header_len,
+            data_len,
+            position: 0,
+        })
+    }
+    pub fn into_inner(self) -> (H, D) {
+        (self.header, self.data)
+    }
+}
+
+impl <H: Read + Seek, D: Read + Seek> Read for AppleDoubleFile<H, D> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Self { header, data, header_len, position, .. } = self;
+        let bytes = if *position < *header_len {
+            let budget = (*header_len - *position).min(buf.len() as u64) as usize;
+            header.seek(SeekFrom::Start(*position))?;
+            header.read(&mut buf[..budget])?
+        } else {
+            data.seek(SeekFrom::Start(*position - *header_len))?;
+            data.read(buf)?
+        };
+        *position += bytes as u64;
+        Ok(bytes)
+    }
+}
+
+impl <H: Seek, D: Seek> Seek for AppleDoubleFile<H, D> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.header_len + self.data_len;
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => len.checked_add_signed(delta),
+        };
+        self.position = position.ok_or(io::ErrorKind::InvalidInput)?;
+        Ok(self.position)
+    }
+}
+
+/// Writes both halves of an AppleDouble pair: the header file holding the
+/// metadata and resource fork, and the plain data file.
+pub struct AppleDoubleWriter<'a> {
+    entries: Vec<EntrySource<'a>>,
+    rsrc_fork: Option<EntrySource<'a>>,
+    data_fork: Option<Box<dyn Read + 'a>>,
+}
+
+impl <'a> AppleDoubleWriter<'a> {
+    pub fn new(archive: &Archive) -> io::Result<Self> {
+        Self::with_finder_info(archive, applesingle::finder_info_bytes(archive)?)
+    }
+    /// Starts a header file for `archive` whose Finder info has already been
+    /// encoded by [`applesingle::finder_info_bytes`].
+    pub(crate) fn with_finder_info(archive: &Archive, finder_info: Option<Vec<u8>>) -> io::Result<Self> {
+        Ok(Self {
+            entries: applesingle::metadata_entries_with(archive, finder_info)?,
+            rsrc_fork: None,
+            data_fork: None,
+        })
+    }
+    pub fn rsrc_fork<R: Read + 'a>(&mut self, fork: R, len: Option<u64>) -> &Self {
+        let id = EntryType::ResourceFork.into();
+        self.rsrc_fork = Some(EntrySource::new(id, len, fork));
+        self
+    }
+    pub fn data_fork<R: Read + 'a>(&mut self, fork: R) -> &Self {
+        self.data_fork = Some(Box::new(fork));
+        self
+    }
+    /// Writes the header file to `header` and the data fork to `data`, which
+    /// requires that the length of the resource fork is known.
+    pub fn write<H: Write, D: Write>(self, header: H, data: D) -> io::Result<()> {
+        let Self { mut entries, rsrc_fork, data_fork } = self;
+        entries.extend(rsrc_fork);
+        applesingle::write_archive(header, APPLEDOUBLE_MAGIC, entries)?;
+        Self::write_data(data_fork, data)
+    }
+    /// Writes the header file to `header` and the data fork to `data`,
+    /// measuring the resource fork as it is written if its length is unknown.
+    pub fn write_seekable<H: Write + Seek, D: Write>(self, header: H, data: D) -> io::Result<()> {
+        let Self { mut entries, rsrc_fork, data_fork } = self;
+        entries.extend(rsrc_fork);
+        applesingle::write_archive_seekable(header, APPLEDOUBLE_MAGIC, entries)?;
+        Self::write_data(data_fork, data)
+    }
+    fn write_data<D: Write>(data_fork: Option<Box<dyn Read + 'a>>, mut data: D) -> io::Result<()> {
+        if let Some(mut fork) = data_fork {
+            io::copy(&mut fork, &mut data)?;
+        }
+        Ok(())
+    }
+    /// Writes the data fork to `path` and the header file next to it.
+    pub fn save<P: AsRef<Path>>(self, path: P) -> io::Result<()> {
+        self.save_as(path, Layout::DotUnderscore)
+    }
+    /// Writes the data fork to `path` and the header file wherever `layout`
+    /// keeps it.
+    pub fn save_as<P: AsRef<Path>>(self, path: P, layout: Layout) -> io::Result<()> {
+        let path = path.as_ref();
+        let header_path = layout.header_path(path)
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        create_parent(&header_path)?;
+        let header = File::create(header_path)?;
+        let data = File::create(path)?;
+        self.write_seekable(header, data)
+    }
+    /// Writes only the header file for the existing file or folder at
+    /// `path`, wherever `layout` keeps it.
+    pub fn save_header<P: AsRef<Path>>(self, path: P, layout: Layout) -> io::Result<()> {
+        let path = path.as_ref();
+        let header_path = layout.header_path_for(path, path.is_dir())
+            .ok_or(io::ErrorKind::InvalidInput)?;
+        create_parent(&header_path)?;
+        let Self { mut entries, rsrc_fork, .. } = self;
+        entries.extend(rsrc_fork);
+        applesingle::write_archive_seekable(File::create(header_path)?, APPLEDOUBLE_MAGIC, entries)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{MultiForkArchive, testing};
+
+    #[test]
+    fn rejects_entry_running_into_data_file() {
+        // 26 + 12 bytes of table, then the 4-byte name, then the data file
+        let header = testing::raw(APPLEDOUBLE_MAGIC, None, &[(EntryType::RealName, 38, 8)], b"name");
+        let e = parse_seekable(Cursor::new(header), Cursor::new(b"data".to_vec()))
+            .err()
+            .expect("the name runs into the data file");
+        assert!(matches!(e, Error::EntryOutOfBounds { id: 3, offset: 38, len: 8 }), "{e:?}");
+    }
+
+    #[test]
+    fn replaces_data_fork_entry_of_header() {
+        let header = testing::raw(APPLEDOUBLE_MAGIC, None, &[
+            (EntryType::RealName, 50, 4),
+            (EntryType::DataFork, 54, 5),
+        ], b"namestale");
+        let mut archive = parse_seekable(
+            Cursor::new(header),
+            Cursor::new(b"data".to_vec()),
+        ).unwrap();
+        let data_forks: Vec<&Entry> = archive.entries().iter()
+            .filter(|entry| entry.entry_type() == Some(EntryType::DataFork))
+            .collect();
+        assert_eq!(data_forks.len(), 1);
+        assert_eq!((data_forks[0].offset(), data_forks[0].len()), (59, 4));
+        let mut data = vec![];
+        archive.data_fork().unwrap().unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"data");
+    }
+}
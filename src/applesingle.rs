@@ -1,5 +1,5 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     fmt,
     io::{
         self,
@@ -13,39 +13,66 @@ use num_enum::{TryFromPrimitive, IntoPrimitive};
 use deku::prelude::*;
 
 use super::{
+    AFPDirectoryID,
+    AFPInfo,
+    AFPShortName,
     Comment,
+    Date,
     Dates,
     Entry,
+    ExtendedFinderInfo,
     Filename,
     FinderInfo,
     MacInfo,
+    MSDOSInfo,
+    MultiForkArchive,
+    ProDOSInfo,
+    loose,
     archive::{
         Archive,
+        ArchiveBuilder,
         SeekableArchive,
+        SeekableArchiveBuilder,
     },
+    format::Format,
     io::{
         ReadExt as _,
         CountingReader,
     },
+    error::{self, Error},
+    ArchiveWriter,
+    Header,
 };
 
-const FORMAT_NAME: &str = "AppleSingle";
+pub(crate) const APPLESINGLE_MAGIC: u32 = 0x0005_1600;
+pub(crate) const APPLEDOUBLE_MAGIC: u32 = 0x0005_1607;
+const VERSION_1: u32 = 0x0001_0000;
+const VERSION_2: u32 = 0x0002_0000;
+
+const HEADER_LEN: usize = 26;
+const SEGMENT_LEN: usize = 12;
+/// The longest metadata entry the streaming parser will hold in memory.
+const MAX_CAPTURE_LEN: u64 = 64 * 1024;
 
 #[derive(
     Debug,
+    Clone, Copy,
     PartialEq, Eq,
     PartialOrd, Ord,
     TryFromPrimitive, IntoPrimitive,
 )]
 #[repr(u32)]
-enum EntryType {
+pub enum EntryType {
     DataFork = 1,
     ResourceFork,
     RealName,
     Comment,
     IconBW,
     IconColor,
-    FileDates = 8,
+    /// Only found in version 1 files, with contents that depend on the
+    /// [`HomeFileSystem`].
+    FileInfo,
+    FileDates,
     FinderInfo,
     MacintoshFileInfo,
     ProDOSFileInfo,
@@ -55,13 +82,100 @@ enum EntryType {
     AFPDirectoryID,
 }
 
+impl EntryType {
+    /// Whether the ID is defined for files of the given version. Version 1
+    /// has only the forks, the name, comment and icons, File Info and Finder
+    /// Info; version 2 replaces File Info with the rest.
+    fn defined_in(&self, version_1: bool) -> bool {
+        match self {
+            Self::FileInfo => version_1,
+            Self::DataFork
+            | Self::ResourceFork
+            | Self::RealName
+            | Self::Comment
+            | Self::IconBW
+            | Self::IconColor
+            | Self::FinderInfo => true,
+            _ => !version_1,
+        }
+    }
+}
+
+/// The fixed-size header shared by AppleSingle and AppleDouble files, which
+/// only differ in their magic number.
 #[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
-#[deku(endian = "big", magic = b"\x00\x05\x16\x00\x00\x02\x00\x00")]
+#[deku(endian = "big")]
 struct AppleSingleHeader {
-    #[deku(pad_bytes_before = "16")]
+    magic: u32,
+    version: u32,
+    /// Holds the [`HomeFileSystem`] in version 1 files, filler otherwise.
+    home_file_system: [u8; 16],
     n_segments: u16,
 }
 
+/// The file system a version 1 AppleSingle or AppleDouble file was created
+/// on, which determines the layout of its File Info entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeFileSystem {
+    Macintosh,
+    ProDOS,
+    MSDOS,
+    Unix,
+    VAXVMS,
+    Other([u8; 16]),
+}
+
+impl From<[u8; 16]> for HomeFileSystem {
+    fn from(name: [u8; 16]) -> Self {
+        match &name {
+            b"Macintosh       " => Self::Macintosh,
+            b"ProDOS          " => Self::ProDOS,
+            b"MS-DOS          " => Self::MSDOS,
+            b"Unix            " => Self::Unix,
+            b"VAX VMS         " => Self::VAXVMS,
+            _ => Self::Other(name),
+        }
+    }
+}
+
+impl fmt::Display for HomeFileSystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Macintosh => write!(f, "Macintosh"),
+            Self::ProDOS => write!(f, "ProDOS"),
+            Self::MSDOS => write!(f, "MS-DOS"),
+            Self::Unix => write!(f, "Unix"),
+            Self::VAXVMS => write!(f, "VAX VMS"),
+            Self::Other(name) => write!(f, "{}", String::from_utf8_lossy(name).trim_end()),
+        }
+    }
+}
+
+/// The File Info entry of version 1 files created on a Macintosh, which
+/// combines the dates and attributes that version 2 splits into separate
+/// entries.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+struct MacFileInfo {
+    #[deku(endian = "big")]
+    created: u32,
+    #[deku(endian = "big")]
+    modified: u32,
+    #[deku(endian = "big")]
+    backed_up: u32,
+    attributes: MacInfo,
+}
+
+impl MacFileInfo {
+    fn dates(&self) -> Dates {
+        Dates {
+            create: Date::from_classic(self.created),
+            modify: Date::from_classic(self.modified),
+            backup: Date::from_classic(self.backed_up),
+            access: Date::UNKNOWN,
+        }
+    }
+}
+
 #[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
 #[deku(endian = "big")]
 pub struct Segment {
@@ -74,6 +188,21 @@ impl Segment {
     fn entry_type(&self) -> Option<EntryType> {
         self.id.try_into().ok()
     }
+    /// Whether the entry is decoded into the [`Archive`] itself, rather than
+    /// being a fork or some other entry handed to a [`Handler`].
+    fn is_metadata(&self, home_file_system: Option<HomeFileSystem>) -> bool {
+        match self.entry_type() {
+            Some(EntryType::FileInfo) => home_file_system == Some(HomeFileSystem::Macintosh),
+            Some(
+                EntryType::DataFork
+                | EntryType::ResourceFork
+                | EntryType::IconBW
+                | EntryType::IconColor
+            ) => false,
+            Some(_) => true,
+            None => false,
+        }
+    }
     pub fn offset_u64(&self) -> u64 {
         self.offset as u64
     }
@@ -83,63 +212,184 @@ impl Segment {
     pub fn len_u64(&self) -> u64 {
         self.len as u64
     }
-    fn wrap<R: Read>(&self, reader: &mut R) -> io::Result<ArchiveMember> {
-        let len = self.len_usize();
+    /// The length the specification requires of the entry, if it has a fixed
+    /// size.
+    fn fixed_len(&self, home_file_system: Option<HomeFileSystem>) -> Option<u64> {
+        match self.entry_type()? {
+            EntryType::FileInfo if home_file_system == Some(HomeFileSystem::Macintosh) => Some(16),
+            EntryType::FileDates => Some(16),
+            EntryType::FinderInfo => Some(32),
+            EntryType::MacintoshFileInfo => Some(4),
+            EntryType::ProDOSFileInfo => Some(8),
+            EntryType::MSDOSFileInfo => Some(2),
+            EntryType::AFPFileInfo => Some(4),
+            EntryType::AFPDirectoryID => Some(4),
+            _ => None,
+        }
+    }
+    /// Makes sure an entry is large enough to hold a fixed-size structure.
+    fn require_len(&self, expected: u64) -> error::Result<()> {
+        if self.len_u64() < expected {
+            return Err(Error::InvalidEntryLength {
+                id: self.id,
+                offset: self.offset_u64(),
+                expected,
+                found: self.len_u64(),
+            });
+        }
+        Ok(())
+    }
+    fn wrap<R: Read>(
+        &self,
+        reader: &mut R,
+        home_file_system: Option<HomeFileSystem>,
+    ) -> error::Result<ArchiveMember> {
+        let entry: Entry = (*self).into();
+        self.decode(reader, home_file_system)
+            .map_err(|e| e.within(&entry))
+    }
+    fn decode<R: Read>(
+        &self,
+        reader: &mut R,
+        home_file_system: Option<HomeFileSystem>,
+    ) -> error::Result<ArchiveMember> {
         let entry: Entry = (*self).into();
         let member = match self.entry_type() {
             Some(EntryType::RealName) => {
-                let mut buf = Vec::with_capacity(len);
-                reader.read_to_end(&mut buf)?;
-                ArchiveMember::RealName(Filename(buf))
+                ArchiveMember::RealName(Filename(self.read_all(reader)?))
             },
             Some(EntryType::Comment) => {
-                let mut buf = Vec::with_capacity(len);
-                reader.read_to_end(&mut buf)?;
-                ArchiveMember::Comment(Comment(buf))
+                ArchiveMember::Comment(Comment(self.read_all(reader)?))
             },
             Some(EntryType::FinderInfo) => {
-                let mut buf = [0u8; 16];
-                reader.read_exact(&mut buf)?;
-                let (_, info) = FinderInfo::from_bytes((&buf, 0))?;
-                ArchiveMember::FinderInfo(info)
+                // some writers only include the first half of the entry,
+                // others follow it with even more data.
+                self.require_len(16)?;
+                let mut buf = Vec::with_capacity(32);
+                reader.take(32).read_to_end(&mut buf)?;
+                if buf.len() < 16 {
+                    Err(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                }
+                let (_, info) = FinderInfo::from_bytes((&buf[..16], 0))?;
+                let extended = match buf.get(16..32) {
+                    Some(buf) => Some(ExtendedFinderInfo::from_bytes((buf, 0))?.1),
+                    None => None,
+                };
+                ArchiveMember::FinderInfo(info, extended)
             },
             Some(EntryType::FileDates) => {
+                self.require_len(16)?;
                 let mut buf = [0u8; 16];
                 reader.read_exact(&mut buf)?;
                 let (_, dates) = Dates::from_bytes((&buf, 0))?;
                 ArchiveMember::FileDates(dates)
             },
+            Some(EntryType::FileInfo)
+                if home_file_system == Some(HomeFileSystem::Macintosh) => {
+                self.require_len(16)?;
+                let mut buf = [0u8; 16];
+                reader.read_exact(&mut buf)?;
+                let (_, info) = MacFileInfo::from_bytes((&buf, 0))?;
+                ArchiveMember::FileInfo(info.dates(), info.attributes)
+            },
             Some(EntryType::MacintoshFileInfo) => {
+                self.require_len(4)?;
                 let mut buf = [0u8; 4];
                 reader.read_exact(&mut buf)?;
                 let (_, info) = MacInfo::from_bytes((&buf, 0))?;
                 ArchiveMember::MacInfo(info)
             },
+            Some(EntryType::ProDOSFileInfo) => {
+                self.require_len(8)?;
+                let mut buf = [0u8; 8];
+                reader.read_exact(&mut buf)?;
+                let (_, info) = ProDOSInfo::from_bytes((&buf, 0))?;
+                ArchiveMember::ProDOSInfo(info)
+            },
+            Some(EntryType::MSDOSFileInfo) => {
+                self.require_len(2)?;
+                let mut buf = [0u8; 2];
+                reader.read_exact(&mut buf)?;
+                let (_, info) = MSDOSInfo::from_bytes((&buf, 0))?;
+                ArchiveMember::MSDOSInfo(info)
+            },
+            Some(EntryType::AFPShortName) => {
+                ArchiveMember::AFPShortName(AFPShortName(self.read_all(reader)?))
+            },
+            Some(EntryType::AFPFileInfo) => {
+                self.require_len(4)?;
+                let mut buf = [0u8; 4];
+                reader.read_exact(&mut buf)?;
+                let (_, info) = AFPInfo::from_bytes((&buf, 0))?;
+                ArchiveMember::AFPInfo(info)
+            },
+            Some(EntryType::AFPDirectoryID) => {
+                self.require_len(4)?;
+                let mut buf = [0u8; 4];
+                reader.read_exact(&mut buf)?;
+                let (_, id) = AFPDirectoryID::from_bytes((&buf, 0))?;
+                ArchiveMember::AFPDirectoryID(id)
+            },
             Some(EntryType::ResourceFork) => ArchiveMember::ResourceFork(entry),
             Some(EntryType::DataFork) => ArchiveMember::DataFork(entry),
             _ => ArchiveMember::Other(entry),
         };
         Ok(member)
     }
+    /// Reads the whole of a variable-length entry. Its declared length is
+    /// not trusted to size the buffer, since the bytes may not be there.
+    fn read_all<R: Read>(&self, reader: &mut R) -> error::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        reader.take(self.len_u64()).read_to_end(&mut buf)?;
+        if buf.len() < self.len_usize() {
+            Err(io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        }
+        Ok(buf)
+    }
+}
+
+impl From<Segment> for Entry {
+    fn from(segment: Segment) -> Self {
+        let Segment { id, offset, len } = segment;
+        Self {
+            id,
+            offset: offset as u64,
+            len: len as u64,
+        }
+    }
 }
 
-impl Into<Entry> for Segment {
-    fn into(self) -> Entry {
-        let Self { id, offset, len } = self;
-        Entry { id, offset, len }
+impl TryFrom<Entry> for Segment {
+    type Error = io::Error;
+    fn try_from(entry: Entry) -> io::Result<Self> {
+        let Entry { id, offset, len } = entry;
+        let too_large = |_| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "entry does not fit in a 32-bit archive",
+        );
+        Ok(Self {
+            id,
+            offset: offset.try_into().map_err(too_large)?,
+            len: len.try_into().map_err(too_large)?,
+        })
     }
 }
 
 #[derive(Default)]
 struct ArchiveHeader {
-    segments: BTreeMap<u32, Segment>,
+    home_file_system: Option<HomeFileSystem>,
+    /// Every segment in the entry table, in the order they appear.
+    segments: Vec<Segment>,
 }
 
 impl ArchiveHeader {
+    /// The segments sorted by offset. Only the last of any segments sharing
+    /// an ID is kept.
     fn segments_by_offset(&self) -> Vec<Segment> {
-        let mut segments: Vec<Segment> = self.segments.values()
-            .cloned()
+        let by_id: BTreeMap<u32, Segment> = self.segments.iter()
+            .map(|segment| (segment.id, *segment))
             .collect();
+        let mut segments: Vec<Segment> = by_id.into_values().collect();
         segments.sort_by_key(|s| s.offset);
         segments
     }
@@ -151,11 +401,66 @@ pub enum ArchiveMember {
     RealName(Filename),
     Comment(Comment),
     FileDates(Dates),
-    FinderInfo(FinderInfo),
+    FinderInfo(FinderInfo, Option<ExtendedFinderInfo>),
     MacInfo(MacInfo),
+    FileInfo(Dates, MacInfo),
+    ProDOSInfo(ProDOSInfo),
+    MSDOSInfo(MSDOSInfo),
+    AFPShortName(AFPShortName),
+    AFPInfo(AFPInfo),
+    AFPDirectoryID(AFPDirectoryID),
     Other(Entry),
 }
 
+impl ArchiveMember {
+    /// Records a decoded entry in the metadata of an archive. Forks and
+    /// other entries are left for the caller.
+    fn apply(self, builder: &mut ArchiveBuilder) {
+        match self {
+            Self::RealName(name) => {
+                builder.name(name);
+            }
+            Self::Comment(comment) => {
+                builder.comment(comment);
+            }
+            Self::FinderInfo(finf, fxinf) => {
+                builder.finf(finf);
+                if let Some(fxinf) = fxinf {
+                    builder.fxinf(fxinf);
+                }
+            }
+            Self::MacInfo(minf) => {
+                builder.minf(minf);
+            }
+            Self::FileDates(date) => {
+                builder.date(date);
+            }
+            Self::FileInfo(date, minf) => {
+                builder.date(date);
+                builder.minf(minf);
+            }
+            Self::ProDOSInfo(info) => {
+                builder.prodos_info(info);
+            }
+            Self::MSDOSInfo(info) => {
+                builder.msdos_info(info);
+            }
+            Self::AFPShortName(name) => {
+                builder.afp_short_name(name);
+            }
+            Self::AFPInfo(info) => {
+                builder.afp_info(info);
+            }
+            Self::AFPDirectoryID(id) => {
+                builder.afp_directory_id(id);
+            }
+            Self::DataFork(_)
+            | Self::ResourceFork(_)
+            | Self::Other(_) => {},
+        }
+    }
+}
+
 impl fmt::Debug for ArchiveMember {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -164,8 +469,14 @@ impl fmt::Debug for ArchiveMember {
             Self::RealName(filename) => write!(f, "RealName({filename})"),
             Self::Comment(comment) =>  write!(f, "Comment({comment})"),
             Self::FileDates(dates) => write!(f, "FileDates({dates:?})"),
-            Self::FinderInfo(info) => write!(f, "FinderInfo({info:?})"),
+            Self::FinderInfo(info, extended) => write!(f, "FinderInfo({info:?}, {extended:?})"),
             Self::MacInfo(info) => write!(f, "MacInfo({})", info),
+            Self::FileInfo(dates, info) => write!(f, "FileInfo({dates:?}, {info})"),
+            Self::ProDOSInfo(info) => write!(f, "ProDOSInfo({info:?})"),
+            Self::MSDOSInfo(info) => write!(f, "MSDOSInfo({info})"),
+            Self::AFPShortName(name) => write!(f, "AFPShortName({name})"),
+            Self::AFPInfo(info) => write!(f, "AFPInfo({info})"),
+            Self::AFPDirectoryID(id) => write!(f, "AFPDirectoryID({})", id.0),
             Self::Other(entry) => write!(f, "Other({entry:?})"),
         }
     }
@@ -174,57 +485,172 @@ impl fmt::Debug for ArchiveMember {
 struct AppleSingleArchiveReader<R> {
     reader: CountingReader<R>,
     header: ArchiveHeader,
+    /// The raw header and entry table, which some entries point back into.
+    table: Vec<u8>,
+    captures: Vec<Capture>,
 }
 
 impl <R: Read> AppleSingleArchiveReader<R> {
-    fn streaming(reader: R) -> io::Result<Self> {
+    fn streaming(reader: R, magic: u32) -> error::Result<Self> {
         let mut archive = Self {
             reader: reader.counting(),
             header: ArchiveHeader::default(),
+            table: vec![],
+            captures: vec![],
         };
-        archive.read_header()?;
+        archive.read_header(magic)?;
         Ok(archive)
     }
-    fn read_header(&mut self) -> io::Result<()> {
-        let mut bytes = [0u8; 26];
-        self.read_exact(&mut bytes)?;
+    fn read_header(&mut self, magic: u32) -> error::Result<()> {
+        let mut bytes = [0u8; HEADER_LEN];
+        self.read_table(&mut bytes)?;
         let (_, header) = AppleSingleHeader::from_bytes((&bytes, 0))?;
-        let AppleSingleHeader { n_segments } = header;
+        let AppleSingleHeader {
+            magic: found,
+            version,
+            home_file_system,
+            n_segments,
+        } = header;
+        if found != magic {
+            return Err(Error::BadMagic { found });
+        }
+        match version {
+            VERSION_1 => {
+                self.header.home_file_system = Some(home_file_system.into());
+            },
+            VERSION_2 => {},
+            _ => return Err(Error::UnsupportedVersion { version }),
+        }
         for _ in 0..n_segments {
             self.read_segment()?;
         }
         Ok(())
     }
-    fn read_segment(&mut self) -> io::Result<()> {
-        let mut bytes = [0u8; 12];
-        self.read_exact(&mut bytes)?;
+    fn read_segment(&mut self) -> error::Result<()> {
+        let mut bytes = [0u8; SEGMENT_LEN];
+        self.read_table(&mut bytes)?;
         let (_, segment) = Segment::from_bytes((&bytes, 0))?;
-        self.header.segments.insert(segment.id, segment);
+        self.header.segments.push(segment);
+        Ok(())
+    }
+    /// Reads part of the header or entry table, which must not be cut short.
+    fn read_table(&mut self, bytes: &mut [u8]) -> error::Result<()> {
+        let offset = self.position();
+        self.read_exact(bytes).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::TruncatedHeader { offset },
+            _ => e.into(),
+        })?;
+        self.table.extend_from_slice(bytes);
         Ok(())
     }
+    fn position(&self) -> u64 {
+        self.reader.position()
+    }
     fn segments_by_offset(&self) -> Vec<Segment> {
         self.header.segments_by_offset()
     }
-    pub fn skip_to(&mut self, offset: u64) -> io::Result<()> {
-        self.reader.skip_to(offset)?;
+    fn home_file_system(&self) -> Option<HomeFileSystem> {
+        self.header.home_file_system
+    }
+    fn skip_to(&mut self, offset: u64) -> io::Result<()> {
+        let position = self.position();
+        if offset > position {
+            let skipped = io::copy(&mut self.take(offset - position), &mut io::sink())?;
+            if skipped < offset - position {
+                Err(io::ErrorKind::UnexpectedEof)?;
+            }
+        }
+        Ok(())
+    }
+    /// Starts collecting the contents of `segments` as the bytes they cover
+    /// are read, including any which lie within the entry table. Only the
+    /// part of a fixed-size entry that gets decoded is kept, so anything
+    /// trailing it is skipped, while entries of no fixed size longer than
+    /// [`MAX_CAPTURE_LEN`] are rejected rather than buffered.
+    fn capture(&mut self, segments: Vec<Segment>) -> error::Result<()> {
+        let home_file_system = self.home_file_system();
+        for segment in segments {
+            let len = segment.len_u64();
+            let limit = match segment.fixed_len(home_file_system) {
+                Some(fixed_len) => fixed_len.min(len),
+                None if len > MAX_CAPTURE_LEN => return Err(Error::EntryTooLarge {
+                    id: segment.id,
+                    offset: segment.offset_u64(),
+                    len,
+                    limit: MAX_CAPTURE_LEN,
+                }),
+                None => len,
+            };
+            let mut capture = Capture::new(segment, limit);
+            capture.feed(0, &self.table);
+            self.captures.push(capture);
+        }
+        Ok(())
+    }
+    /// Reads up to the end of every captured segment and hands them over.
+    fn finish_captures(&mut self) -> error::Result<Vec<Capture>> {
+        let mut ends: Vec<(u64, Entry)> = self.captures.iter()
+            .map(|capture| (capture.end(), capture.segment.into()))
+            .collect();
+        ends.sort_by_key(|(end, _)| *end);
+        for (end, entry) in ends {
+            self.skip_to(end)
+                .map_err(|e| Error::from(e).within(&entry))?;
+        }
+        Ok(std::mem::take(&mut self.captures))
+    }
+    /// Reads the entire contents of a fork into `sink`. Forks which start
+    /// before the current position can only be recovered if they lie within
+    /// the entry table, since nothing else that has been read is kept.
+    fn copy_entry(
+        &mut self,
+        entry: &Entry,
+        previous: Option<u32>,
+        sink: &mut dyn Write,
+    ) -> error::Result<()> {
+        let Entry { id, offset, len } = *entry;
+        if len == 0 {
+            return Ok(());
+        }
+        if offset < self.position() {
+            let end = offset + len;
+            if end <= self.table.len() as u64 {
+                sink.write_all(&self.table[offset as usize..end as usize])?;
+                return Ok(());
+            }
+            return Err(Error::OverlappingEntries { id, previous, offset });
+        }
+        self.skip_to(offset)
+            .map_err(|e| Error::from(e).within(entry))?;
+        let copied = io::copy(&mut entry.limit(&mut *self)?, sink)?;
+        if copied < len {
+            return Err(Error::out_of_bounds(entry));
+        }
         Ok(())
     }
 }
 
 impl <R: Read + Seek> AppleSingleArchiveReader<R> {
-    fn seekable(reader: R) -> io::Result<Self> {
+    fn seekable(reader: R, magic: u32) -> error::Result<Self> {
         let mut archive = Self {
             reader: reader.counting(),
             header: ArchiveHeader::default(),
+            table: vec![],
+            captures: vec![],
         };
-        archive.read_header()?;
+        archive.read_header(magic)?;
         Ok(archive)
     }
 }
 
 impl <R: Read> Read for AppleSingleArchiveReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.reader.read(buf)
+        let offset = self.reader.position();
+        let bytes = self.reader.read(buf)?;
+        for capture in &mut self.captures {
+            capture.feed(offset, &buf[..bytes]);
+        }
+        Ok(bytes)
     }
 }
 
@@ -234,20 +660,40 @@ impl <S: Seek> Seek for AppleSingleArchiveReader<S> {
     }
 }
 
-struct SegmentReader<'a, R: Read> {
+/// The contents of a metadata entry, collected as the stream goes by so that
+/// it can share bytes with other entries.
+struct Capture {
     segment: Segment,
-    reader: io::Take<&'a mut AppleSingleArchiveReader<R>>,
+    /// How many bytes from the start of the entry are kept.
+    limit: u64,
+    bytes: Vec<u8>,
 }
 
-impl <'a, R: Read> SegmentReader<'a, R> {
-    fn from_segment(segment: Segment, reader: &'a mut AppleSingleArchiveReader<R>) -> io::Result<Self> {
-        reader.skip_to(segment.offset_u64())?;
-        let reader = reader.take(segment.len_u64());
-        Ok(Self { segment, reader })
+impl Capture {
+    fn new(segment: Segment, limit: u64) -> Self {
+        Self {
+            segment,
+            limit,
+            bytes: vec![],
+        }
     }
-    fn wrap(self) -> io::Result<ArchiveMember> {
-        let Self { segment, mut reader } = self;
-        segment.wrap(&mut reader)
+    /// Where the whole entry ends, which must still be reached for it to be
+    /// complete.
+    fn end(&self) -> u64 {
+        self.segment.offset_u64() + self.segment.len_u64()
+    }
+    /// Keeps whatever part of `buf`, found at `offset` in the stream, picks
+    /// up where the collected bytes leave off.
+    fn feed(&mut self, offset: u64, buf: &[u8]) {
+        let next = self.segment.offset_u64() + self.bytes.len() as u64;
+        let kept_end = self.segment.offset_u64() + self.limit;
+        let buf_end = offset + buf.len() as u64;
+        if next >= kept_end || next < offset || next >= buf_end {
+            return;
+        }
+        let start = (next - offset) as usize;
+        let end = (kept_end.min(buf_end) - offset) as usize;
+        self.bytes.extend_from_slice(&buf[start..end]);
     }
 }
 
@@ -263,69 +709,121 @@ pub trait Handler {
 pub fn parse<R: Read, H: Handler>(
     archive: R,
     handler: &mut H,
-) -> io::Result<Archive> {
-    let mut reader = AppleSingleArchiveReader::streaming(archive)?;
-    let segments = reader.segments_by_offset();
+) -> error::Result<Archive> {
+    parse_archive(archive, handler, APPLESINGLE_MAGIC, Format::AppleSingle)
+}
+
+pub fn parse_seekable<R: Read + Seek>(
+    archive: R,
+) -> error::Result<SeekableArchive<R>> {
+    parse_seekable_archive(archive, APPLESINGLE_MAGIC, Format::AppleSingle)
+}
+
+pub(crate) fn parse_archive<R: Read, H: Handler>(
+    archive: R,
+    handler: &mut H,
+    magic: u32,
+    format: Format,
+) -> error::Result<Archive> {
+    let mut reader = AppleSingleArchiveReader::streaming(archive, magic)?;
+    let home_file_system = reader.home_file_system();
+    let (metadata, forks): (Vec<Segment>, Vec<Segment>) = reader.segments_by_offset()
+        .into_iter()
+        .partition(|segment| segment.is_metadata(home_file_system));
     let mut builder = Archive::builder();
-    builder.format(FORMAT_NAME.into());
-    for segment in segments {
-        let member = SegmentReader::from_segment(segment, &mut reader)
-            .and_then(SegmentReader::wrap)?;
-        match member {
-            ArchiveMember::ResourceFork(segment) => {
-                if let Some(mut sink) = handler.sink(Fork::Rsrc) {
-                    let mut fork = Entry::from(segment)
-                        .limit(&mut reader)?;
-                    io::copy(&mut fork, &mut sink)?;
-                }
-            },
-            ArchiveMember::DataFork(segment) => {
-                if let Some(mut sink) = handler.sink(Fork::Data) {
-                    let mut fork = Entry::from(segment)
-                        .limit(&mut reader)?;
-                    io::copy(&mut fork, &mut sink)?;
-                }
-            },
-            ArchiveMember::Other(segment) => {
-                if let Some(mut sink) = handler.sink(Fork::Other(segment.id)) {
-                    let mut fork = Entry::from(segment)
-                        .limit(&mut reader)?;
-                    io::copy(&mut fork, &mut sink)?;
-                }
-            },
-            ArchiveMember::RealName(name) => {
-                builder.name(name);
-            }
-            ArchiveMember::Comment(comment) => {
-                builder.comment(comment);
-            }
-            ArchiveMember::FinderInfo(finf) => {
-                builder.finf(finf);
-            }
-            ArchiveMember::MacInfo(minf) => {
-                builder.minf(minf);
-            }
-            ArchiveMember::FileDates(date) => {
-                builder.date(date);
-            }
+    builder.format(format);
+    if let Some(home_file_system) = home_file_system {
+        builder.home_file_system(home_file_system);
+    }
+    // metadata is gathered as the forks stream past, so it is only decoded
+    // once everything has been read.
+    reader.capture(metadata)?;
+    let mut previous = None;
+    for segment in forks {
+        let fork = match segment.entry_type() {
+            Some(EntryType::DataFork) => Fork::Data,
+            Some(EntryType::ResourceFork) => Fork::Rsrc,
+            _ => Fork::Other(segment.id),
+        };
+        if let Some(mut sink) = handler.sink(fork) {
+            reader.copy_entry(&segment.into(), previous, &mut sink)?;
+            previous = Some(segment.id);
         }
     }
+    for Capture { segment, bytes, .. } in reader.finish_captures()? {
+        // forks were streamed to the handler above
+        segment.wrap(&mut bytes.as_slice(), home_file_system)?
+            .apply(&mut builder);
+    }
 
     builder.build()
-        .ok_or(io::ErrorKind::Other.into())
+        .ok_or(Error::Incomplete)
 }
 
-pub fn parse_seekable<R: Read + Seek>(
-    mut archive: R,
-) -> io::Result<SeekableArchive<R>> {
-    let segments = AppleSingleArchiveReader::seekable(&mut archive)?
-        .segments_by_offset();
+pub(crate) fn parse_seekable_archive<R: Read + Seek>(
+    archive: R,
+    magic: u32,
+    format: Format,
+) -> error::Result<SeekableArchive<R>> {
     let mut builder = SeekableArchive::builder(archive);
-    builder.format(FORMAT_NAME.into());
-    for segment in segments {
+    parse_seekable_entries(&mut builder, magic, format)?;
+    builder.build()
+        .ok_or(Error::Incomplete)
+}
+
+pub(crate) fn parse_seekable_entries<R: Read + Seek>(
+    builder: &mut SeekableArchiveBuilder<R>,
+    magic: u32,
+    format: Format,
+) -> error::Result<()> {
+    let file_len = builder.file().seek(SeekFrom::End(0))?;
+    builder.file().rewind()?;
+    parse_entries(builder, magic, format, file_len, true)
+}
+
+/// Parses the entries of an AppleDouble header file which takes up the first
+/// `header_len` bytes of the builder's file, followed by its data file. The
+/// entries must lie within the header file, and any data fork entry of its
+/// own is left out so that the caller can point at the data file instead.
+pub(crate) fn parse_header_entries<R: Read + Seek>(
+    builder: &mut SeekableArchiveBuilder<R>,
+    format: Format,
+    header_len: u64,
+) -> error::Result<()> {
+    parse_entries(builder, APPLEDOUBLE_MAGIC, format, header_len, false)
+}
+
+fn parse_entries<R: Read + Seek>(
+    builder: &mut SeekableArchiveBuilder<R>,
+    magic: u32,
+    format: Format,
+    len: u64,
+    keep_data_fork: bool,
+) -> error::Result<()> {
+    let (table, segments, home_file_system) = {
+        let reader = AppleSingleArchiveReader::seekable(builder.file(), magic)?;
+        (reader.header.segments.clone(), reader.segments_by_offset(), reader.home_file_system())
+    };
+    builder.archive().format(format);
+    if let Some(home_file_system) = home_file_system {
+        builder.archive().home_file_system(home_file_system);
+    }
+    let skip = |segment: &Segment| {
+        !keep_data_fork && segment.entry_type() == Some(EntryType::DataFork)
+    };
+    // The whole table is kept, in order and with any repeated IDs, while
+    // only the last of the entries sharing an ID is decoded.
+    for segment in table.iter().filter(|segment| !skip(segment)) {
+        let entry: Entry = (*segment).into();
+        if entry.offset + entry.len > len {
+            return Err(Error::out_of_bounds(&entry));
+        }
+        builder.table_entry(entry);
+    }
+    for segment in segments.into_iter().filter(|segment| !skip(segment)) {
         let member = {
             let mut reader = builder.entry(segment.into())?;
-            segment.wrap(&mut reader)?
+            segment.wrap(&mut reader, home_file_system)?
         };
         match member {
             ArchiveMember::ResourceFork(entry) => {
@@ -334,26 +832,741 @@ pub fn parse_seekable<R: Read + Seek>(
             ArchiveMember::DataFork(entry) => {
                 builder.data_fork(entry);
             },
-            ArchiveMember::Other(_) => {
-                // TODO: expose non-resource/data forks somehow
-            },
-            ArchiveMember::RealName(name) => {
-                builder.name(name);
-            }
-            ArchiveMember::Comment(comment) => {
-                builder.comment(comment);
-            }
-            ArchiveMember::FinderInfo(finf) => {
-                builder.finf(finf);
-            }
-            ArchiveMember::MacInfo(minf) => {
-                builder.minf(minf);
+            // only available through the entry table
+            ArchiveMember::Other(_) => {},
+            member => member.apply(builder.archive()),
+        };
+    }
+    Ok(())
+}
+
+/// A way in which an AppleSingle or AppleDouble file departs from the
+/// specification, as reported by [`validate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// The filler following the version number of a version 2 file is not
+    /// zeroed.
+    NonZeroFiller,
+    /// Another entry with the same ID appears earlier in the entry table.
+    /// Only the last of them is used when parsing.
+    DuplicateEntry {
+        id: u32,
+        offset: u64,
+    },
+    /// The ID is not one defined for this version of the format.
+    UnknownEntry {
+        id: u32,
+        offset: u64,
+    },
+    /// The entry extends past the end of the file.
+    EntryOutOfBounds {
+        id: u32,
+        offset: u64,
+        len: u64,
+    },
+    /// The entry shares bytes with an earlier entry, or with the entry table
+    /// when `previous` is `None`.
+    OverlappingEntries {
+        id: u32,
+        previous: Option<u32>,
+        offset: u64,
+    },
+    /// An entry with a fixed size has the wrong length. AppleDouble files
+    /// may have longer Finder info, as those written by macOS do.
+    InvalidEntryLength {
+        id: u32,
+        offset: u64,
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NonZeroFiller => write!(f, "filler is not zeroed"),
+            Self::DuplicateEntry { id, offset } => write!(
+                f,
+                "entry {id} at offset {offset} duplicates an earlier entry",
+            ),
+            Self::UnknownEntry { id, offset } => write!(
+                f,
+                "entry {id} at offset {offset} has an unknown ID",
+            ),
+            Self::EntryOutOfBounds { id, offset, len } => write!(
+                f,
+                "entry {id} at offset {offset} with length {len} extends past the end of the file",
+            ),
+            Self::OverlappingEntries { id, previous: Some(previous), offset } => write!(
+                f,
+                "entry {id} at offset {offset} overlaps entry {previous}",
+            ),
+            Self::OverlappingEntries { id, previous: None, offset } => write!(
+                f,
+                "entry {id} at offset {offset} overlaps the entry table",
+            ),
+            Self::InvalidEntryLength { id, offset, expected, found } => write!(
+                f,
+                "entry {id} at offset {offset} has length {found}, expected {expected}",
+            ),
+        }
+    }
+}
+
+/// Checks an AppleSingle file against the specification, reporting every
+/// violation rather than stopping at the first. Only a file whose header
+/// cannot be read at all is an error.
+pub fn validate<R: Read>(archive: R) -> error::Result<Vec<Violation>> {
+    validate_archive(archive, APPLESINGLE_MAGIC)
+}
+
+pub(crate) fn validate_archive<R: Read>(
+    archive: R,
+    magic: u32,
+) -> error::Result<Vec<Violation>> {
+    let mut reader = AppleSingleArchiveReader::streaming(archive, magic)?;
+    let table_len = reader.position();
+    let file_len = table_len + io::copy(&mut reader, &mut io::sink())?;
+    let home_file_system = reader.home_file_system();
+    let mut violations = vec![];
+
+    // the home file system takes the place of the filler in version 1.
+    if home_file_system.is_none() && reader.table[8..24].iter().any(|b| *b != 0) {
+        violations.push(Violation::NonZeroFiller);
+    }
+
+    let segments = &reader.header.segments;
+    let mut seen = BTreeSet::new();
+    for segment in segments {
+        let (id, offset, len) = (segment.id, segment.offset_u64(), segment.len_u64());
+        if !seen.insert(id) {
+            violations.push(Violation::DuplicateEntry { id, offset });
+        }
+        // only version 1 files have a home file system.
+        let known = segment.entry_type()
+            .is_some_and(|entry_type| entry_type.defined_in(home_file_system.is_some()));
+        if !known {
+            violations.push(Violation::UnknownEntry { id, offset });
+        }
+        if offset + len > file_len {
+            violations.push(Violation::EntryOutOfBounds { id, offset, len });
+        }
+        if let Some(expected) = segment.fixed_len(home_file_system) {
+            // macOS keeps extended attributes after the Finder info of the
+            // AppleDouble files it writes.
+            let extended = magic == APPLEDOUBLE_MAGIC
+                && segment.entry_type() == Some(EntryType::FinderInfo)
+                && len > expected;
+            if len != expected && !extended {
+                violations.push(Violation::InvalidEntryLength {
+                    id,
+                    offset,
+                    expected,
+                    found: len,
+                });
             }
-            ArchiveMember::FileDates(date) => {
-                builder.date(date);
+        }
+    }
+
+    let mut sorted: Vec<&Segment> = segments.iter()
+        .filter(|segment| segment.len > 0)
+        .collect();
+    sorted.sort_by_key(|segment| segment.offset);
+    let (mut furthest, mut previous) = (table_len, None);
+    for segment in sorted {
+        let offset = segment.offset_u64();
+        if offset < furthest {
+            violations.push(Violation::OverlappingEntries {
+                id: segment.id,
+                previous,
+                offset,
+            });
+        }
+        let end = offset + segment.len_u64();
+        if end > furthest {
+            furthest = end;
+            previous = Some(segment.id);
+        }
+    }
+
+    Ok(violations)
+}
+
+/// An entry waiting to be copied into an archive being written. Its length
+/// need not be known when the archive is written to a seekable sink.
+pub(crate) struct EntrySource<'a> {
+    id: u32,
+    len: Option<u64>,
+    source: Box<dyn Read + 'a>,
+}
+
+impl <'a> EntrySource<'a> {
+    pub(crate) fn new<R: Read + 'a>(id: u32, len: Option<u64>, source: R) -> Self {
+        Self { id, len, source: Box::new(source) }
+    }
+    fn buffer(entry_type: EntryType, bytes: Vec<u8>) -> Self {
+        let len = bytes.len() as u64;
+        Self::new(entry_type.into(), Some(len), io::Cursor::new(bytes))
+    }
+}
+
+/// Encodes the Finder info of a file or folder along with its extended
+/// counterpart, which is filled with zeroes if missing.
+pub(crate) fn finder_info_bytes(archive: &Archive) -> io::Result<Option<Vec<u8>>> {
+    if let Some(dinf) = archive.folder_info() {
+        let dxinf = archive.extended_folder_info().unwrap_or_default();
+        let mut bytes = dinf.to_bytes()?;
+        bytes.extend(dxinf.to_bytes()?);
+        Ok(Some(bytes))
+    } else if let Some(finf) = archive.finder_info() {
+        let fxinf = archive.extended_finder_info().unwrap_or_default();
+        let mut bytes = finf.to_bytes()?;
+        bytes.extend(fxinf.to_bytes()?);
+        Ok(Some(bytes))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Encodes all of the metadata held by an [`Archive`] as entries, in the
+/// order recommended by the AppleSingle specification.
+pub(crate) fn metadata_entries(archive: &Archive) -> io::Result<Vec<EntrySource<'static>>> {
+    metadata_entries_with(archive, finder_info_bytes(archive)?)
+}
+
+/// Encodes the metadata of `archive` as [`metadata_entries`] does, taking the
+/// Finder info as already encoded by [`finder_info_bytes`].
+pub(crate) fn metadata_entries_with(
+    archive: &Archive,
+    finder_info: Option<Vec<u8>>,
+) -> io::Result<Vec<EntrySource<'static>>> {
+    let mut entries = vec![];
+    if let Some(Filename(name)) = archive.name() {
+        entries.push(EntrySource::buffer(EntryType::RealName, name));
+    }
+    if let Some(Comment(comment)) = archive.comment() {
+        entries.push(EntrySource::buffer(EntryType::Comment, comment));
+    }
+    if let Some(dates) = archive.dates() {
+        entries.push(EntrySource::buffer(EntryType::FileDates, dates.to_bytes()?));
+    }
+    if let Some(bytes) = finder_info {
+        entries.push(EntrySource::buffer(EntryType::FinderInfo, bytes));
+    }
+    if let Some(minf) = archive.mac_info() {
+        entries.push(EntrySource::buffer(EntryType::MacintoshFileInfo, minf.to_bytes()?));
+    }
+    if let Some(info) = archive.prodos_info() {
+        entries.push(EntrySource::buffer(EntryType::ProDOSFileInfo, info.to_bytes()?));
+    }
+    if let Some(info) = archive.msdos_info() {
+        entries.push(EntrySource::buffer(EntryType::MSDOSFileInfo, info.to_bytes()?));
+    }
+    if let Some(AFPShortName(name)) = archive.afp_short_name() {
+        entries.push(EntrySource::buffer(EntryType::AFPShortName, name));
+    }
+    if let Some(info) = archive.afp_info() {
+        entries.push(EntrySource::buffer(EntryType::AFPFileInfo, info.to_bytes()?));
+    }
+    if let Some(id) = archive.afp_directory_id() {
+        entries.push(EntrySource::buffer(EntryType::AFPDirectoryID, id.to_bytes()?));
+    }
+    Ok(entries)
+}
+
+/// Encodes the fixed header and entry table of an archive.
+fn entry_table(magic: u32, entries: &[Entry]) -> io::Result<Vec<u8>> {
+    let n_segments = entries.len().try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many entries"))?;
+    let header = AppleSingleHeader {
+        magic,
+        version: VERSION_2,
+        home_file_system: [0; 16],
+        n_segments,
+    };
+    let mut bytes = header.to_bytes()?;
+    for entry in entries {
+        let segment = Segment::try_from(*entry)?;
+        bytes.extend(segment.to_bytes()?);
+    }
+    Ok(bytes)
+}
+
+/// Writes a complete archive with the given magic number, placing the
+/// entries one after another right behind the entry table. The length of
+/// every entry must be known up front.
+pub(crate) fn write_archive<W: Write>(
+    file: W,
+    magic: u32,
+    entries: Vec<EntrySource>,
+) -> io::Result<W> {
+    let mut header = Header::default();
+    let mut offset = (HEADER_LEN + SEGMENT_LEN * entries.len()) as u64;
+    for EntrySource { id, len, .. } in &entries {
+        let len = len.ok_or_else(|| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("length of entry {id} must be known"),
+        ))?;
+        header.add_entry(Entry { id: *id, offset, len });
+        offset += len;
+    }
+    let mut writer = ArchiveWriter::new(header, file);
+    let table = entry_table(magic, writer.entries())?;
+    writer.write_all(&table)?;
+    for (index, EntrySource { source, .. }) in entries.into_iter().enumerate() {
+        writer.write_entry(index, source)?;
+    }
+    Ok(writer.into_inner())
+}
+
+/// Writes a complete archive like [`write_archive`], except that entries of
+/// unknown length are allowed and the entry table is filled in once they have
+/// all been written. Entries whose length is given must still be that long.
+pub(crate) fn write_archive_seekable<W: Write + Seek>(
+    file: W,
+    magic: u32,
+    entries: Vec<EntrySource>,
+) -> io::Result<W> {
+    if entries.iter().all(|entry| entry.len.is_some()) {
+        return write_archive(file, magic, entries);
+    }
+    let mut header = Header::default();
+    for EntrySource { id, .. } in &entries {
+        header.add_entry(Entry { id: *id, offset: 0, len: 0 });
+    }
+    let mut writer = ArchiveWriter::new(header, file);
+    let placeholder = entry_table(magic, writer.entries())?;
+    writer.write_all(&placeholder)?;
+    for (index, EntrySource { len, source, .. }) in entries.into_iter().enumerate() {
+        match len {
+            Some(len) => {
+                writer.append_entry(index, source.take(len))?;
+                if writer.entries()[index].len < len {
+                    Err(io::ErrorKind::UnexpectedEof)?;
+                }
+            },
+            None => writer.append_entry(index, source)?,
+        }
+    }
+    let table = entry_table(magic, writer.entries())?;
+    writer.rewrite(0, &table)?;
+    Ok(writer.into_inner())
+}
+
+/// Builds an AppleSingle file out of the metadata of an [`Archive`], its
+/// forks and any other entries.
+pub struct AppleSingleWriter<'a> {
+    entries: Vec<EntrySource<'a>>,
+    rsrc_fork: Option<EntrySource<'a>>,
+    data_fork: Option<EntrySource<'a>>,
+}
+
+impl <'a> AppleSingleWriter<'a> {
+    pub fn new(archive: &Archive) -> io::Result<Self> {
+        Ok(Self {
+            entries: metadata_entries(archive)?,
+            rsrc_fork: None,
+            data_fork: None,
+        })
+    }
+    pub fn data_fork<R: Read + 'a>(&mut self, fork: R, len: Option<u64>) -> &Self {
+        self.entry(EntryType::DataFork.into(), fork, len)
+    }
+    pub fn rsrc_fork<R: Read + 'a>(&mut self, fork: R, len: Option<u64>) -> &Self {
+        self.entry(EntryType::ResourceFork.into(), fork, len)
+    }
+    /// Adds an entry with an arbitrary ID, replacing any entry which already
+    /// has that ID.
+    pub fn entry<R: Read + 'a>(&mut self, id: u32, data: R, len: Option<u64>) -> &Self {
+        let entry = EntrySource::new(id, len, data);
+        match id.try_into() {
+            Ok(EntryType::DataFork) => self.data_fork = Some(entry),
+            Ok(EntryType::ResourceFork) => self.rsrc_fork = Some(entry),
+            _ => {
+                self.entries.retain(|entry| entry.id != id);
+                self.entries.push(entry);
+            },
+        }
+        self
+    }
+    fn into_entries(self) -> Vec<EntrySource<'a>> {
+        let Self { mut entries, rsrc_fork, data_fork } = self;
+        entries.extend(rsrc_fork);
+        entries.extend(data_fork);
+        entries
+    }
+    /// Writes the archive to a stream, which requires that the length of
+    /// every entry is known.
+    pub fn write<W: Write>(self, out: W) -> io::Result<W> {
+        write_archive(out, APPLESINGLE_MAGIC, self.into_entries())
+    }
+    /// Writes the archive to a seekable stream, measuring any entries whose
+    /// length was not given as they are written.
+    pub fn write_seekable<W: Write + Seek>(self, out: W) -> io::Result<W> {
+        write_archive_seekable(out, APPLESINGLE_MAGIC, self.into_entries())
+    }
+}
+
+/// Writes an AppleSingle file holding the metadata of `archive` and the
+/// given forks.
+pub fn write<W: Write + Seek>(
+    archive: &Archive,
+    data_fork: Option<&mut dyn Read>,
+    rsrc_fork: Option<&mut dyn Read>,
+    out: W,
+) -> io::Result<W> {
+    let mut writer = AppleSingleWriter::new(archive)?;
+    if let Some(fork) = rsrc_fork {
+        writer.rsrc_fork(fork, None);
+    }
+    if let Some(fork) = data_fork {
+        writer.data_fork(fork, None);
+    }
+    writer.write_seekable(out)
+}
+
+/// Writes an AppleSingle file holding everything `archive` knows about a
+/// file, whatever format it was read from. The resource fork is read into
+/// memory and the data fork streamed.
+pub fn repack<A: MultiForkArchive + ?Sized, W: Write + Seek>(
+    archive: &mut A,
+    out: W,
+) -> error::Result<W> {
+    let rsrc = loose::read_rsrc_fork(archive)?;
+    let mut writer = AppleSingleWriter::new(archive.archive())?;
+    if !rsrc.is_empty() {
+        let len = rsrc.len() as u64;
+        writer.rsrc_fork(io::Cursor::new(rsrc), Some(len));
+    }
+    if let Some(fork) = archive.data_fork()? {
+        writer.data_fork(fork, None);
+    }
+    Ok(writer.write_seekable(out)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Creator, FileType, appledouble::{self, AppleDoubleWriter}, testing};
+
+    /// Where the entries of a file with `n` of them begin.
+    fn table_len(n: usize) -> u32 {
+        (HEADER_LEN + SEGMENT_LEN * n) as u32
+    }
+
+    /// Lays out an AppleSingle file. See [`testing::raw`].
+    fn raw(
+        home_file_system: Option<&[u8; 16]>,
+        segments: &[(EntryType, u32, u32)],
+        body: &[u8],
+    ) -> Vec<u8> {
+        testing::raw(APPLESINGLE_MAGIC, home_file_system, segments, body)
+    }
+
+    /// Collects the forks of a streamed archive.
+    #[derive(Default)]
+    struct Forks {
+        data: Vec<u8>,
+        rsrc: Vec<u8>,
+    }
+
+    impl Handler for Forks {
+        fn sink<'a>(&'a mut self, fork: Fork) -> Option<Box<dyn Write + 'a>> {
+            match fork {
+                Fork::Data => Some(Box::new(&mut self.data)),
+                Fork::Rsrc => Some(Box::new(&mut self.rsrc)),
+                Fork::Other(_) => None,
             }
-        };
+        }
+    }
+
+    #[test]
+    fn streams_oversized_finder_info() {
+        // macOS keeps extended attributes at the end of the Finder info.
+        let start = table_len(2);
+        let finder_info_len = 32 + MAX_CAPTURE_LEN as u32 * 2;
+        let mut body = b"TEXTttxt".to_vec();
+        body.resize(finder_info_len as usize, 0xAA);
+        body.extend_from_slice(b"data");
+        let file = raw(None, &[
+            (EntryType::FinderInfo, start, finder_info_len),
+            (EntryType::DataFork, start + finder_info_len, 4),
+        ], &body);
+
+        let mut forks = Forks::default();
+        let archive = parse(file.as_slice(), &mut forks).unwrap();
+        let finf = archive.finder_info().unwrap();
+        assert_eq!(finf.file_type, FileType::from(*b"TEXT"));
+        assert_eq!(finf.creator, Creator::from(*b"ttxt"));
+        let fxinf = archive.extended_finder_info().unwrap();
+        assert_eq!(fxinf.to_bytes().unwrap(), [0xAA; 16]);
+        assert_eq!(forks.data, b"data");
+    }
+
+    #[test]
+    fn streams_entries_sharing_bytes() {
+        let start = table_len(3);
+        let file = raw(None, &[
+            // the magic number and version, found in the entry table
+            (EntryType::RealName, 0, 8),
+            // the end of the comment
+            (EntryType::AFPShortName, start + 3, 3),
+            (EntryType::Comment, start, 6),
+        ], b"abcdef");
+
+        let archive = parse(file.as_slice(), &mut Forks::default()).unwrap();
+        assert_eq!(archive.name().unwrap().as_bytes(), &file[..8]);
+        assert_eq!(archive.comment().unwrap().0, b"abcdef");
+        assert_eq!(archive.afp_short_name().unwrap().0, b"def");
+    }
+
+    fn sample_archive() -> Archive {
+        let mut builder = Archive::builder();
+        builder.format(Format::AppleSingle);
+        builder.name(Filename(b"Read Me".to_vec()));
+        builder.comment(Comment(b"hello".to_vec()));
+        builder.date(Dates {
+            create: Date::from_classic(0x1000),
+            modify: Date::from_classic(0x2000),
+            backup: Date::UNKNOWN,
+            access: Date::from_classic(0x3000),
+        });
+        let finf = *b"TEXTttxt\x01\x00\x00\x10\x00\x20\x00\x00";
+        builder.finf(FinderInfo::from_bytes((&finf, 0)).unwrap().1);
+        builder.build().unwrap()
+    }
+
+    fn read_fork(fork: Option<Box<dyn Read + '_>>) -> Vec<u8> {
+        let mut bytes = vec![];
+        fork.unwrap().read_to_end(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn assert_same_metadata(a: &Archive, b: &Archive) {
+        assert_eq!(a.name().map(|n| n.0), b.name().map(|n| n.0));
+        assert_eq!(a.comment().map(|c| c.0), b.comment().map(|c| c.0));
+        assert_eq!(a.dates(), b.dates());
+        assert_eq!(a.finder_info(), b.finder_info());
+    }
+
+    #[test]
+    fn writer_round_trips_through_parser() {
+        let original = sample_archive();
+        for seekable in [false, true] {
+            let mut writer = AppleSingleWriter::new(&original).unwrap();
+            writer.rsrc_fork(&b"rsrc"[..], Some(4));
+            writer.data_fork(&b"data fork"[..], (!seekable).then_some(9));
+            writer.entry(0x8000_0000, &b"extra"[..], Some(5));
+            let file = match seekable {
+                true => writer.write_seekable(io::Cursor::new(vec![])).unwrap().into_inner(),
+                false => writer.write(vec![]).unwrap(),
+            };
+
+            let mut archive = parse_seekable(io::Cursor::new(file)).unwrap();
+            assert_same_metadata(archive.archive(), &original);
+            let ids: Vec<u32> = archive.entries().iter().map(Entry::id).collect();
+            assert_eq!(ids, [3, 4, 8, 9, 0x8000_0000, 2, 1]);
+            assert_eq!(read_fork(archive.rsrc_fork().unwrap()), b"rsrc");
+            assert_eq!(read_fork(archive.data_fork().unwrap()), b"data fork");
+            assert_eq!(read_fork(archive.entry_by_id(0x8000_0000).unwrap()), b"extra");
+        }
+    }
+
+    #[test]
+    fn appledouble_writer_round_trips_through_parser() {
+        let original = sample_archive();
+        let mut writer = AppleDoubleWriter::new(&original).unwrap();
+        writer.rsrc_fork(&b"rsrc"[..], None);
+        writer.data_fork(&b"data fork"[..]);
+        let (mut header, mut data) = (io::Cursor::new(vec![]), vec![]);
+        writer.write_seekable(&mut header, &mut data).unwrap();
+        assert_eq!(data, b"data fork");
+
+        let mut archive = appledouble::parse_seekable(header, io::Cursor::new(data)).unwrap();
+        assert_same_metadata(archive.archive(), &original);
+        let ids: Vec<u32> = archive.entries().iter().map(Entry::id).collect();
+        assert_eq!(ids, [3, 4, 8, 9, 2, 1]);
+        assert_eq!(read_fork(archive.rsrc_fork().unwrap()), b"rsrc");
+        assert_eq!(read_fork(archive.data_fork().unwrap()), b"data fork");
+    }
+
+    #[test]
+    fn writer_rejects_short_entries() {
+        let original = sample_archive();
+        for seekable in [false, true] {
+            let mut writer = AppleSingleWriter::new(&original).unwrap();
+            // an entry of unknown length forces the seekable writer to
+            // patch the entry table afterwards
+            writer.rsrc_fork(&b"rsrc"[..], (!seekable).then_some(4));
+            writer.data_fork(&b"short"[..], Some(9));
+            let e = match seekable {
+                true => writer.write_seekable(io::Cursor::new(vec![])).unwrap_err(),
+                false => writer.write(vec![]).unwrap_err(),
+            };
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    /// A version 1 File Info entry for a Macintosh file which is locked.
+    const MAC_FILE_INFO: [u8; 16] = [
+        0, 0, 0x10, 0,
+        0, 0, 0x20, 0,
+        0, 0, 0x30, 0,
+        0, 0, 0, 1,
+    ];
+
+    #[test]
+    fn parses_version_1_file_info() {
+        let start = table_len(2);
+        let mut body = MAC_FILE_INFO.to_vec();
+        body.extend_from_slice(b"data");
+        let file = raw(Some(b"Macintosh       "), &[
+            (EntryType::FileInfo, start, 16),
+            (EntryType::DataFork, start + 16, 4),
+        ], &body);
+
+        let mut forks = Forks::default();
+        let streamed = parse(file.as_slice(), &mut forks).unwrap();
+        assert_eq!(forks.data, b"data");
+        let seekable = parse_seekable(io::Cursor::new(file)).unwrap().into_archive();
+        for archive in [streamed, seekable] {
+            assert_eq!(archive.home_file_system(), Some(HomeFileSystem::Macintosh));
+            assert_eq!(archive.dates(), Some(Dates {
+                create: Date::from_classic(0x1000),
+                modify: Date::from_classic(0x2000),
+                backup: Date::from_classic(0x3000),
+                access: Date::UNKNOWN,
+            }));
+            let minf = archive.mac_info().unwrap();
+            assert!(minf.is_locked && !minf.is_protected);
+        }
+    }
+
+    #[test]
+    fn leaves_file_info_undecoded_in_version_2() {
+        let file = raw(None, &[(EntryType::FileInfo, table_len(1), 16)], &MAC_FILE_INFO);
+
+        let streamed = parse(file.as_slice(), &mut Forks::default()).unwrap();
+        let seekable = parse_seekable(io::Cursor::new(file.as_slice())).unwrap();
+        assert_eq!(seekable.entries().len(), 1);
+        for archive in [&streamed, seekable.archive()] {
+            assert_eq!(archive.dates(), None);
+            assert!(archive.mac_info().is_none());
+        }
+        assert_eq!(validate(file.as_slice()).unwrap(), [
+            Violation::UnknownEntry { id: 7, offset: table_len(1) as u64 },
+        ]);
+    }
+
+    #[test]
+    fn validates_conforming_file() {
+        let start = table_len(3);
+        let file = raw(None, &[
+            (EntryType::RealName, start, 4),
+            (EntryType::FinderInfo, start + 4, 32),
+            (EntryType::DataFork, start + 36, 4),
+        ], &[0; 40]);
+        assert_eq!(validate(file.as_slice()).unwrap(), []);
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let start = table_len(6);
+        let mut file = raw(None, &[
+            (EntryType::RealName, start, 4),
+            (EntryType::RealName, start + 4, 4),
+            // overlaps the second name
+            (EntryType::Comment, start + 6, 4),
+            (EntryType::FileDates, start + 10, 8),
+            // overlaps the entry table
+            (EntryType::FileInfo, 4, 16),
+            (EntryType::DataFork, start + 18, 100),
+        ], &[0; 18]);
+        file[10] = 1;
+        assert_eq!(validate(file.as_slice()).unwrap(), [
+            Violation::NonZeroFiller,
+            Violation::DuplicateEntry { id: 3, offset: start as u64 + 4 },
+            Violation::InvalidEntryLength {
+                id: 8,
+                offset: start as u64 + 10,
+                expected: 16,
+                found: 8,
+            },
+            Violation::UnknownEntry { id: 7, offset: 4 },
+            Violation::EntryOutOfBounds { id: 1, offset: start as u64 + 18, len: 100 },
+            Violation::OverlappingEntries { id: 7, previous: None, offset: 4 },
+            Violation::OverlappingEntries {
+                id: 4,
+                previous: Some(3),
+                offset: start as u64 + 6,
+            },
+        ]);
+    }
+
+    #[test]
+    fn reports_version_2_entries_in_version_1() {
+        let file = raw(
+            Some(b"Macintosh       "),
+            &[(EntryType::FileDates, table_len(1), 16)],
+            &[0; 16],
+        );
+        assert_eq!(validate(file.as_slice()).unwrap(), [
+            Violation::UnknownEntry { id: 8, offset: table_len(1) as u64 },
+        ]);
+    }
+
+    #[test]
+    fn allows_extended_finder_info_only_in_appledouble() {
+        let mut file = raw(None, &[(EntryType::FinderInfo, table_len(1), 3810)], &[0; 3810]);
+        assert_eq!(validate(file.as_slice()).unwrap(), [Violation::InvalidEntryLength {
+            id: 9,
+            offset: table_len(1) as u64,
+            expected: 32,
+            found: 3810,
+        }]);
+        file[..4].copy_from_slice(&APPLEDOUBLE_MAGIC.to_be_bytes());
+        assert_eq!(appledouble::validate(file.as_slice()).unwrap(), []);
+    }
+
+    #[test]
+    fn rejects_oversized_name_while_streaming() {
+        let len = MAX_CAPTURE_LEN as u32 + 1;
+        let file = raw(None, &[(EntryType::RealName, table_len(1), len)], &[]);
+        let e = parse(file.as_slice(), &mut Forks::default()).unwrap_err();
+        assert!(matches!(
+            e,
+            Error::EntryTooLarge { id: 3, limit: MAX_CAPTURE_LEN, .. },
+        ), "{e:?}");
+    }
+
+    #[test]
+    fn reads_only_what_entries_hold() {
+        let segment = Segment { id: EntryType::RealName.into(), offset: 0, len: u32::MAX };
+        let e = segment.read_all(&mut &b"name"[..]).unwrap_err();
+        assert!(matches!(e, Error::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof), "{e:?}");
+
+        let segment = Segment { len: 4, ..segment };
+        assert_eq!(segment.read_all(&mut &b"namedata"[..]).unwrap(), b"name");
+    }
+
+    #[test]
+    fn keeps_every_entry_sharing_an_id() {
+        let start = table_len(3);
+        let file = raw(None, &[
+            (EntryType::RealName, start, 5),
+            (EntryType::RealName, start + 5, 4),
+            (EntryType::DataFork, start + 9, 4),
+        ], b"firstlastdata");
+        let mut archive = parse_seekable(io::Cursor::new(file)).unwrap();
+        assert_eq!(archive.name().unwrap().as_bytes(), b"last");
+        let entries = archive.entries().to_vec();
+        assert_eq!(entries.len(), 3);
+        let ids: Vec<u32> = entries.iter().map(Entry::id).collect();
+        assert_eq!(ids, [3, 3, 1]);
+        for (entry, expected) in entries.iter().zip([&b"first"[..], b"last", b"data"]) {
+            let mut contents = vec![];
+            archive.entry(entry).unwrap().read_to_end(&mut contents).unwrap();
+            assert_eq!(contents, expected);
+        }
     }
-    builder.build()
-        .ok_or(io::ErrorKind::Other.into())
 }
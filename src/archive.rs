@@ -1,26 +1,41 @@
 use std::io::{Read, Seek, Result};
-use derive_more::{From, Into, Display};
 
 use super::{
+    ExtendedFinderInfo,
+    ExtendedFolderInfo,
     FinderInfo,
+    FolderInfo,
     MacInfo,
     Filename,
     Dates,
     Comment,
     Entry,
+    AFPDirectoryID,
+    AFPInfo,
+    AFPShortName,
+    MSDOSInfo,
+    ProDOSInfo,
+    applesingle::HomeFileSystem,
+    error,
+    format::Format,
 };
 
-#[derive(Debug, Clone, Copy, From, Into, Display)]
-#[display(fmt = "{}", _0)]
-pub struct Format(&'static str);
-
 pub struct ArchiveBuilder {
     format: Option<Format>,
     finf: Option<FinderInfo>,
+    fxinf: Option<ExtendedFinderInfo>,
+    dinf: Option<FolderInfo>,
+    dxinf: Option<ExtendedFolderInfo>,
     minf: Option<MacInfo>,
     name: Option<Filename>,
     date: Option<Dates>,
     comment: Option<Comment>,
+    home_file_system: Option<HomeFileSystem>,
+    prodos_info: Option<ProDOSInfo>,
+    msdos_info: Option<MSDOSInfo>,
+    afp_short_name: Option<AFPShortName>,
+    afp_info: Option<AFPInfo>,
+    afp_directory_id: Option<AFPDirectoryID>,
 }
 
 impl ArchiveBuilder {
@@ -28,10 +43,19 @@ impl ArchiveBuilder {
         Self {
             format: None,
             finf: None,
+            fxinf: None,
+            dinf: None,
+            dxinf: None,
             minf: None,
             name: None,
             date: None,
             comment: None,
+            home_file_system: None,
+            prodos_info: None,
+            msdos_info: None,
+            afp_short_name: None,
+            afp_info: None,
+            afp_directory_id: None,
         }
     }
     pub fn format(&mut self, format: Format) -> &Self {
@@ -42,6 +66,18 @@ impl ArchiveBuilder {
         self.finf = Some(finf);
         self
     }
+    pub fn fxinf(&mut self, fxinf: ExtendedFinderInfo) -> &Self {
+        self.fxinf = Some(fxinf);
+        self
+    }
+    pub fn dinf(&mut self, dinf: FolderInfo) -> &Self {
+        self.dinf = Some(dinf);
+        self
+    }
+    pub fn dxinf(&mut self, dxinf: ExtendedFolderInfo) -> &Self {
+        self.dxinf = Some(dxinf);
+        self
+    }
     pub fn minf(&mut self, minf: MacInfo) -> &Self {
         self.minf = Some(minf);
         self
@@ -58,14 +94,47 @@ impl ArchiveBuilder {
         self.comment = Some(comment);
         self
     }
+    pub fn home_file_system(&mut self, home_file_system: HomeFileSystem) -> &Self {
+        self.home_file_system = Some(home_file_system);
+        self
+    }
+    pub fn prodos_info(&mut self, prodos_info: ProDOSInfo) -> &Self {
+        self.prodos_info = Some(prodos_info);
+        self
+    }
+    pub fn msdos_info(&mut self, msdos_info: MSDOSInfo) -> &Self {
+        self.msdos_info = Some(msdos_info);
+        self
+    }
+    pub fn afp_short_name(&mut self, afp_short_name: AFPShortName) -> &Self {
+        self.afp_short_name = Some(afp_short_name);
+        self
+    }
+    pub fn afp_info(&mut self, afp_info: AFPInfo) -> &Self {
+        self.afp_info = Some(afp_info);
+        self
+    }
+    pub fn afp_directory_id(&mut self, afp_directory_id: AFPDirectoryID) -> &Self {
+        self.afp_directory_id = Some(afp_directory_id);
+        self
+    }
     pub fn build(&self) -> Option<Archive> {
         let archive = Archive {
             format: self.format?,
             finf: self.finf,
+            fxinf: self.fxinf,
+            dinf: self.dinf,
+            dxinf: self.dxinf,
             minf: self.minf,
             date: self.date,
             name: self.name.clone(),
             comment: self.comment.clone(),
+            home_file_system: self.home_file_system,
+            prodos_info: self.prodos_info,
+            msdos_info: self.msdos_info,
+            afp_short_name: self.afp_short_name.clone(),
+            afp_info: self.afp_info,
+            afp_directory_id: self.afp_directory_id,
         };
         Some(archive)
     }
@@ -75,19 +144,49 @@ impl ArchiveBuilder {
 pub struct Archive {
     format: Format,
     finf: Option<FinderInfo>,
+    fxinf: Option<ExtendedFinderInfo>,
+    dinf: Option<FolderInfo>,
+    dxinf: Option<ExtendedFolderInfo>,
     minf: Option<MacInfo>,
     date: Option<Dates>,
     name: Option<Filename>,
     comment: Option<Comment>,
+    home_file_system: Option<HomeFileSystem>,
+    prodos_info: Option<ProDOSInfo>,
+    msdos_info: Option<MSDOSInfo>,
+    afp_short_name: Option<AFPShortName>,
+    afp_info: Option<AFPInfo>,
+    afp_directory_id: Option<AFPDirectoryID>,
 }
 
 impl Archive {
     pub fn builder() -> ArchiveBuilder {
         ArchiveBuilder::new()
     }
+    /// Reinterprets the Finder info as describing a folder. Nothing in the
+    /// Finder info entry says whether it belongs to a file or a folder, so
+    /// this is up to whoever knows what the archive represents.
+    pub fn into_folder(mut self) -> error::Result<Self> {
+        if let Some(finf) = self.finf.take() {
+            self.dinf = Some(FolderInfo::from_file_info(&finf)?);
+        }
+        if let Some(fxinf) = self.fxinf.take() {
+            self.dxinf = Some(ExtendedFolderInfo::from_file_info(&fxinf)?);
+        }
+        Ok(self)
+    }
     pub fn finder_info(&self) -> Option<FinderInfo> {
         self.finf
     }
+    pub fn extended_finder_info(&self) -> Option<ExtendedFinderInfo> {
+        self.fxinf
+    }
+    pub fn folder_info(&self) -> Option<FolderInfo> {
+        self.dinf
+    }
+    pub fn extended_folder_info(&self) -> Option<ExtendedFolderInfo> {
+        self.dxinf
+    }
     pub fn mac_info(&self) -> Option<MacInfo> {
         self.minf
     }
@@ -103,12 +202,32 @@ impl Archive {
     pub fn format(&self) -> Format {
         self.format
     }
+    /// The file system a version 1 archive was created on.
+    pub fn home_file_system(&self) -> Option<HomeFileSystem> {
+        self.home_file_system
+    }
+    pub fn prodos_info(&self) -> Option<ProDOSInfo> {
+        self.prodos_info
+    }
+    pub fn msdos_info(&self) -> Option<MSDOSInfo> {
+        self.msdos_info
+    }
+    pub fn afp_short_name(&self) -> Option<AFPShortName> {
+        self.afp_short_name.clone()
+    }
+    pub fn afp_info(&self) -> Option<AFPInfo> {
+        self.afp_info
+    }
+    pub fn afp_directory_id(&self) -> Option<AFPDirectoryID> {
+        self.afp_directory_id
+    }
 }
 
 pub struct SeekableArchiveBuilder<R> {
     archive: ArchiveBuilder,
     rsrc_fork: Option<Entry>,
     data_fork: Option<Entry>,
+    entries: Vec<Entry>,
     file: R,
 }
 
@@ -119,34 +238,18 @@ impl <R: Read + Seek> SeekableArchiveBuilder<R> {
             archive: ArchiveBuilder::new(),
             rsrc_fork: None,
             data_fork: None,
+            entries: vec![],
         }
     }
     pub fn entry<'a>(&'a mut self, entry: Entry) -> Result<Box<dyn Read + 'a>> {
         entry.fixate(&mut self.file)
     }
-    pub fn format(&mut self, format: Format) -> &Self {
-        self.archive.format(format);
-        self
-    }
-    pub fn finf(&mut self, finf: FinderInfo) -> &Self {
-        self.archive.finf(finf);
-        self
-    }
-    pub fn minf(&mut self, minf: MacInfo) -> &Self {
-        self.archive.minf(minf);
-        self
-    }
-    pub fn date(&mut self, date: Dates) -> &Self {
-        self.archive.date(date);
-        self
-    }
-    pub fn name(&mut self, name: Filename) -> &Self {
-        self.archive.name(name);
-        self
+    pub(crate) fn file(&mut self) -> &mut R {
+        &mut self.file
     }
-    pub fn comment(&mut self, comment: Comment) -> &Self {
-        self.archive.comment(comment);
-        self
+    /// The builder for the metadata of the archive.
+    pub fn archive(&mut self) -> &mut ArchiveBuilder {
+        &mut self.archive
     }
     pub fn data_fork(&mut self, data: Entry) -> &Self {
         self.data_fork = Some(data);
@@ -156,18 +259,20 @@ impl <R: Read + Seek> SeekableArchiveBuilder<R> {
         self.rsrc_fork = Some(rsrc);
         self
     }
+    /// Records an entry in the archive's entry table, whether or not it has
+    /// been decoded into one of the other fields.
+    pub fn table_entry(&mut self, entry: Entry) -> &Self {
+        self.entries.push(entry);
+        self
+    }
     pub fn build(self) -> Option<SeekableArchive<R>> {
         let archive = self.archive.build()?;
         let archive = SeekableArchive {
-            format: archive.format,
-            finf: archive.finf,
-            minf: archive.minf,
-            date: archive.date,
-            name: archive.name,
-            comment: archive.comment,
+            archive,
             file: self.file,
             rsrc_fork: self.rsrc_fork,
             data_fork: self.data_fork,
+            entries: self.entries,
         };
         Some(archive)
     }
@@ -175,14 +280,10 @@ impl <R: Read + Seek> SeekableArchiveBuilder<R> {
 
 #[derive(Debug)]
 pub struct SeekableArchive<R> {
-    format: Format,
-    finf: Option<FinderInfo>,
-    minf: Option<MacInfo>,
-    date: Option<Dates>,
-    name: Option<Filename>,
-    comment: Option<Comment>,
+    archive: Archive,
     rsrc_fork: Option<Entry>,
     data_fork: Option<Entry>,
+    entries: Vec<Entry>,
     file: R,
 }
 
@@ -190,38 +291,133 @@ impl <R: Read + Seek> SeekableArchive<R> {
     pub fn builder(file: R) -> SeekableArchiveBuilder<R> {
         SeekableArchiveBuilder::new(file)
     }
-    pub fn finder_info(&self) -> Option<FinderInfo> {
-        self.finf
-    }
-    pub fn mac_info(&self) -> Option<MacInfo> {
-        self.minf
-    }
-    pub fn dates(&self) -> Option<Dates> {
-        self.date
-    }
-    pub fn name(&self) -> Option<Filename> {
-        self.name.clone()
-    }
-    pub fn comment(&self) -> Option<Comment> {
-        self.comment.clone()
-    }
-    pub fn format(&self) -> Format {
-        self.format
+    /// Reinterprets the Finder info as describing a folder. Nothing in the
+    /// Finder info entry says whether it belongs to a file or a folder, so
+    /// this is up to whoever knows what the archive represents.
+    pub fn into_folder(mut self) -> error::Result<Self> {
+        self.archive = self.archive.into_folder()?;
+        Ok(self)
     }
-    pub fn data_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>> {
-        if let Some(entry) = self.data_fork {
+    fn open(&mut self, entry: Option<Entry>) -> Result<Option<Box<dyn Read + '_>>> {
+        if let Some(entry) = entry {
             let reader = entry.fixate(&mut self.file)?;
             Ok(Some(reader))
         } else {
             Ok(None)
         }
     }
-    pub fn rsrc_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>> {
-        if let Some(entry) = self.rsrc_fork {
-            let reader = entry.fixate(&mut self.file)?;
-            Ok(Some(reader))
-        } else {
-            Ok(None)
+    /// Every entry in the archive, including those which have no decoded
+    /// representation, ordered by their position in the archive.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+    /// Opens the contents of one of the archive's [entries][Self::entries].
+    pub fn entry<'a>(&'a mut self, entry: &Entry) -> Result<Box<dyn Read + 'a>> {
+        entry.fixate(&mut self.file)
+    }
+    /// Opens the contents of the first entry with the given ID, if any.
+    pub fn entry_by_id<'a>(&'a mut self, id: u32) -> Result<Option<Box<dyn Read + 'a>>> {
+        let entry = self.entries.iter().find(|entry| entry.id == id).copied();
+        self.open(entry)
+    }
+    /// Swaps the file for another holding the same contents.
+    pub(crate) fn map_file<T, F: FnOnce(R) -> T>(self, f: F) -> SeekableArchive<T> {
+        SeekableArchive {
+            archive: self.archive,
+            rsrc_fork: self.rsrc_fork,
+            data_fork: self.data_fork,
+            entries: self.entries,
+            file: f(self.file),
         }
     }
+    /// Discards the file, keeping only the metadata.
+    pub fn into_archive(self) -> Archive {
+        self.archive
+    }
+}
+
+impl <R: Read + Seek> MultiForkArchive for SeekableArchive<R> {
+    fn archive(&self) -> &Archive {
+        &self.archive
+    }
+    fn data_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>> {
+        self.open(self.data_fork)
+    }
+    fn rsrc_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>> {
+        self.open(self.rsrc_fork)
+    }
+}
+
+/// What every archive offers, whatever its format and whether or not its
+/// forks can still be read after parsing.
+pub trait MultiForkArchive {
+    /// The metadata decoded from the archive.
+    fn archive(&self) -> &Archive;
+    /// Opens the data fork, if the archive has one which can still be read.
+    fn data_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>>;
+    /// Opens the resource fork, if the archive has one which can still be
+    /// read.
+    fn rsrc_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>>;
+    fn format(&self) -> Format {
+        self.archive().format()
+    }
+    fn finder_info(&self) -> Option<FinderInfo> {
+        self.archive().finder_info()
+    }
+    fn extended_finder_info(&self) -> Option<ExtendedFinderInfo> {
+        self.archive().extended_finder_info()
+    }
+    fn folder_info(&self) -> Option<FolderInfo> {
+        self.archive().folder_info()
+    }
+    fn extended_folder_info(&self) -> Option<ExtendedFolderInfo> {
+        self.archive().extended_folder_info()
+    }
+    fn mac_info(&self) -> Option<MacInfo> {
+        self.archive().mac_info()
+    }
+    fn dates(&self) -> Option<Dates> {
+        self.archive().dates()
+    }
+    fn name(&self) -> Option<Filename> {
+        self.archive().name()
+    }
+    fn comment(&self) -> Option<Comment> {
+        self.archive().comment()
+    }
+    /// The file system a version 1 archive was created on.
+    fn home_file_system(&self) -> Option<HomeFileSystem> {
+        self.archive().home_file_system()
+    }
+    fn prodos_info(&self) -> Option<ProDOSInfo> {
+        self.archive().prodos_info()
+    }
+    fn msdos_info(&self) -> Option<MSDOSInfo> {
+        self.archive().msdos_info()
+    }
+    fn afp_short_name(&self) -> Option<AFPShortName> {
+        self.archive().afp_short_name()
+    }
+    fn afp_info(&self) -> Option<AFPInfo> {
+        self.archive().afp_info()
+    }
+    fn afp_directory_id(&self) -> Option<AFPDirectoryID> {
+        self.archive().afp_directory_id()
+    }
+}
+
+/// The forks of a streamed archive went to its [`Handler`] while it was
+/// being parsed, so none are left to open.
+///
+/// [`Handler`]: crate::applesingle::Handler
+impl MultiForkArchive for Archive {
+    fn archive(&self) -> &Archive {
+        self
+    }
+    fn data_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>> {
+        Ok(None)
+    }
+    fn rsrc_fork<'a>(&'a mut self) -> Result<Option<Box<dyn Read + 'a>>> {
+        Ok(None)
+    }
 }
@@ -0,0 +1,231 @@
+//! Files as Basilisk II and SheepShaver keep them on the host file system,
+//! with the resource fork in `.rsrc/<name>` and the Finder info in
+//! `.finf/<name>` next to the data file.
+
+use std::{
+    fs::{self, File},
+    io::{self, Seek, prelude::*},
+    path::{Path, PathBuf},
+};
+
+use deku::prelude::*;
+
+use super::{
+    FinderInfo,
+    MultiForkArchive,
+    ResourceFork,
+    SeekableArchive,
+    applesingle,
+    error::{self, Error},
+    format::Format,
+    loose::{self, LooseFile},
+};
+
+/// The length of a `.finf` file: the Finder info followed by the extended
+/// Finder info.
+const FINF_LEN: usize = 32;
+
+/// Finds the path of the `.rsrc` file holding the resource fork of the file
+/// at `path`.
+pub fn rsrc_path<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    helper_path(path.as_ref(), ".rsrc")
+}
+
+/// Finds the path of the `.finf` file holding the Finder info of the file
+/// or folder at `path`.
+pub fn finf_path<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    helper_path(path.as_ref(), ".finf")
+}
+
+fn helper_path(path: &Path, folder: &str) -> Option<PathBuf> {
+    let name = path.file_name()?;
+    Some(path.with_file_name(folder).join(name))
+}
+
+/// Reads a helper file, treating a missing or empty one as absent.
+fn read_helper(path: Option<PathBuf>) -> io::Result<Option<Vec<u8>>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    match fs::read(path) {
+        Ok(bytes) if bytes.is_empty() => Ok(None),
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Creates a helper file, along with the folder that holds it if needed.
+fn create_helper(path: Option<PathBuf>) -> io::Result<File> {
+    let path = path.ok_or(io::ErrorKind::InvalidInput)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    File::create(path)
+}
+
+fn write_helper(path: Option<PathBuf>, contents: &[u8]) -> io::Result<()> {
+    create_helper(path)?.write_all(contents)
+}
+
+/// Reads the Finder info of the file at `path` from its `.finf` file.
+pub fn finder_info<P: AsRef<Path>>(path: P) -> error::Result<Option<FinderInfo>> {
+    let Some(info) = read_helper(finf_path(path))? else {
+        return Ok(None);
+    };
+    let finf = info.get(..16).ok_or(Error::TruncatedHeader { offset: 0 })?;
+    Ok(Some(FinderInfo::from_bytes((finf, 0))?.1))
+}
+
+/// Stores `finder_info` in the `.finf` file of the file at `path`, keeping
+/// the extended Finder info already there.
+pub fn set_finder_info<P: AsRef<Path>>(path: P, finder_info: &FinderInfo) -> io::Result<()> {
+    let path = finf_path(path);
+    let mut info = read_helper(path.clone())?.unwrap_or_default();
+    info.resize(FINF_LEN, 0);
+    info.splice(..16, finder_info.to_bytes()?);
+    write_helper(path, &info)
+}
+
+/// Opens the resource fork of the file at `path` from its `.rsrc` file.
+pub fn resource_fork<P: AsRef<Path>>(path: P) -> io::Result<Option<ResourceFork<File>>> {
+    let Some(path) = rsrc_path(path) else {
+        return Ok(None);
+    };
+    match File::open(path) {
+        Ok(file) => Ok(Some(ResourceFork(file))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Replaces the `.rsrc` file of the file at `path` with the contents of
+/// `fork`, returning its length.
+pub fn set_resource_fork<P: AsRef<Path>, R: Read>(
+    path: P,
+    fork: ResourceFork<R>,
+) -> io::Result<u64> {
+    let ResourceFork(mut fork) = fork;
+    io::copy(&mut fork, &mut create_helper(rsrc_path(path))?)
+}
+
+/// Opens the file or folder at `path` along with its `.rsrc` and `.finf`
+/// files.
+pub fn open<P: AsRef<Path>>(path: P) -> error::Result<SeekableArchive<LooseFile>> {
+    let path = path.as_ref();
+    let rsrc = read_helper(rsrc_path(path))?.unwrap_or_default();
+    let finder_info = read_helper(finf_path(path))?;
+    loose::assemble(path, rsrc, finder_info, Format::Basilisk)
+}
+
+/// Writes the data fork of `archive` to a file at `path`, and its resource
+/// fork and Finder info to the `.rsrc` and `.finf` files beside it.
+pub fn extract<A: MultiForkArchive + ?Sized, P: AsRef<Path>>(
+    archive: &mut A,
+    path: P,
+) -> error::Result<()> {
+    let path = path.as_ref();
+    loose::extract(archive, path, |archive, rsrc| {
+        if !rsrc.is_empty() {
+            write_helper(rsrc_path(path), &rsrc)?;
+        }
+        if let Some(info) = applesingle::finder_info_bytes(archive)? {
+            write_helper(finf_path(path), &info)?;
+        }
+        Ok(())
+    })
+}
+
+/// Packs the file at `path` and its `.rsrc` and `.finf` files into an
+/// AppleSingle file.
+pub fn pack<P: AsRef<Path>, W: Write + Seek>(path: P, out: W) -> error::Result<W> {
+    applesingle::repack(&mut open(path)?, out)
+}
+
+/// Unpacks an AppleSingle file into a data file at `path` and the `.rsrc`
+/// and `.finf` files beside it.
+pub fn unpack<R: Read + Seek, P: AsRef<Path>>(archive: R, path: P) -> error::Result<()> {
+    extract(&mut applesingle::parse_seekable(archive)?, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{Entry, FileType, testing::scratch};
+
+    fn sample_finder_info() -> FinderInfo {
+        let mut finf = [0; 16];
+        finf[..8].copy_from_slice(b"APPLtest");
+        FinderInfo::from_bytes((&finf, 0)).unwrap().1
+    }
+
+    #[test]
+    fn extracts_into_helper_folders() {
+        let root = scratch("basilisk-extract");
+        let mut builder = SeekableArchive::builder(Cursor::new(b"dataresources".to_vec()));
+        builder.archive().format(Format::AppleSingle);
+        builder.archive().finf(sample_finder_info());
+        builder.data_fork(Entry { id: 1, offset: 0, len: 4 });
+        builder.rsrc_fork(Entry { id: 2, offset: 4, len: 9 });
+        let path = root.join("App");
+        extract(&mut builder.build().unwrap(), &path).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+        assert_eq!(fs::read(root.join(".rsrc/App")).unwrap(), b"resources");
+        let finf = fs::read(root.join(".finf/App")).unwrap();
+        assert_eq!(finf.len(), FINF_LEN);
+        assert_eq!(&finf[..8], b"APPLtest");
+
+        let mut archive = open(&path).unwrap();
+        assert_eq!(archive.format(), Format::Basilisk);
+        assert_eq!(archive.name().unwrap().as_bytes(), b"App");
+        assert_eq!(archive.finder_info(), Some(sample_finder_info()));
+        assert_eq!(loose::read_rsrc_fork(&mut archive).unwrap(), b"resources");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn treats_missing_helpers_as_absent() {
+        let root = scratch("basilisk-missing");
+        let path = root.join("Plain");
+        fs::write(&path, b"data").unwrap();
+        assert!(finder_info(&path).unwrap().is_none());
+        assert!(resource_fork(&path).unwrap().is_none());
+
+        // an empty .finf file is as good as none.
+        write_helper(finf_path(&path), b"").unwrap();
+        let mut archive = open(&path).unwrap();
+        assert_eq!(archive.finder_info(), None);
+        assert!(archive.rsrc_fork().unwrap().is_none());
+
+        write_helper(finf_path(&path), b"APPL").unwrap();
+        assert!(matches!(finder_info(&path), Err(Error::TruncatedHeader { .. })));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn keeps_extended_finder_info() {
+        let root = scratch("basilisk-finf");
+        let path = root.join("Doc");
+        let mut info = vec![0; 16];
+        info.extend([0xaa; 16]);
+        write_helper(finf_path(&path), &info).unwrap();
+
+        let mut finf = sample_finder_info();
+        finf.file_type = FileType::from(*b"TEXT");
+        set_finder_info(&path, &finf).unwrap();
+        let info = fs::read(root.join(".finf/Doc")).unwrap();
+        assert_eq!(&info[..4], b"TEXT");
+        assert_eq!(info[16..], [0xaa; 16]);
+        assert_eq!(finder_info(&path).unwrap(), Some(finf));
+
+        assert_eq!(set_resource_fork(&path, ResourceFork(&b"rsrc"[..])).unwrap(), 4);
+        let ResourceFork(mut fork) = resource_fork(&path).unwrap().unwrap();
+        let mut rsrc = vec![];
+        fork.read_to_end(&mut rsrc).unwrap();
+        assert_eq!(rsrc, b"rsrc");
+        fs::remove_dir_all(root).unwrap();
+    }
+}
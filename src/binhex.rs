@@ -0,0 +1,671 @@
+use std::io::{
+    self,
+    BufRead,
+    BufReader,
+    Cursor,
+    prelude::*,
+};
+
+use deku::prelude::*;
+
+use super::{
+    Creator,
+    Entry,
+    FileType,
+    Filename,
+    FinderFlags,
+    FinderInfo,
+    archive::{
+        Archive,
+        ArchiveBuilder,
+        SeekableArchive,
+    },
+    applesingle::{
+        EntryType,
+        Fork,
+        Handler,
+    },
+    error::{self, Error},
+    finder::{
+        Folder,
+        Point,
+    },
+    format::Format,
+    macbinary::CRC,
+};
+
+/// The line which conventionally introduces BinHex data.
+pub(crate) const BANNER: &[u8] = b"(This file must be converted with BinHex";
+
+/// The characters of the 6-bit encoding, in order of their values.
+const ALPHABET: &[u8; 64] = b"!\"#$%&'()*+,-012345689@ABCDEFGHIJKLMNPQRSTUVXYZ[`abcdefhijklmpqr";
+
+/// Marks a run of the previous byte in the run-length compressed stream.
+const RLE_MARKER: u8 = 0x90;
+
+const LINE_LEN: usize = 64;
+
+/// The fixed-size part of the header, which follows the name.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+struct BinHexInfo {
+    version: u8,
+    file_type: FileType,
+    creator: Creator,
+    flags: FinderFlags,
+    #[deku(endian = "big")]
+    data_len: u32,
+    #[deku(endian = "big")]
+    rsrc_len: u32,
+}
+
+const INFO_LEN: usize = 19;
+
+fn decode_char(c: u8) -> Option<u8> {
+    ALPHABET.iter()
+        .position(|a| *a == c)
+        .map(|value| value as u8)
+}
+
+/// Finds whether `text` looks like the start of a BinHex file: either the
+/// banner begins a line before any line of data, or the data itself comes
+/// first. Like the decoder, this allows whitespace before either of them.
+pub(crate) fn detect(text: &[u8]) -> bool {
+    let lines = text.split(|c| *c == b'\n' || *c == b'\r')
+        .map(|line| line.trim_ascii_start())
+        .filter(|line| !line.is_empty());
+    for (n, line) in lines.enumerate() {
+        if line.starts_with(BANNER) {
+            return true;
+        }
+        if line[0] == b':' {
+            return n == 0 && line.get(1).copied().and_then(decode_char).is_some();
+        }
+    }
+    false
+}
+
+/// Turns the 6-bit encoded text into bytes, starting at the first line which
+/// begins with a colon, perhaps after some whitespace, and stopping at the
+/// colon which ends the data.
+struct HqxReader<R> {
+    inner: R,
+    bits: u32,
+    n_bits: u8,
+    done: bool,
+}
+
+impl <R: BufRead> HqxReader<R> {
+    fn new(mut inner: R) -> error::Result<Self> {
+        let mut line_start = true;
+        loop {
+            let buf = inner.fill_buf()?;
+            if buf.is_empty() {
+                return Err(Error::UnrecognizedFormat);
+            }
+            let position = buf.iter().position(|c| {
+                let found = line_start && *c == b':';
+                line_start = *c == b'\n' || *c == b'\r' || (line_start && c.is_ascii_whitespace());
+                found
+            });
+            match position {
+                Some(position) => {
+                    inner.consume(position + 1);
+                    break;
+                },
+                None => {
+                    let len = buf.len();
+                    inner.consume(len);
+                },
+            }
+        }
+        Ok(Self {
+            inner,
+            bits: 0,
+            n_bits: 0,
+            done: false,
+        })
+    }
+}
+
+impl <R: BufRead> Read for HqxReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < out.len() && !self.done {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                // the closing colon is missing, so whatever has been decoded
+                // is all there is.
+                self.done = true;
+                break;
+            }
+            let mut used = 0;
+            for c in buf {
+                if n == out.len() {
+                    break;
+                }
+                used += 1;
+                if *c == b':' {
+                    self.done = true;
+                    break;
+                }
+                if c.is_ascii_whitespace() {
+                    continue;
+                }
+                let value = decode_char(*c).ok_or_else(|| io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid BinHex character {:?}", *c as char),
+                ))?;
+                self.bits = (self.bits << 6) | value as u32;
+                self.n_bits += 6;
+                if self.n_bits >= 8 {
+                    self.n_bits -= 8;
+                    out[n] = (self.bits >> self.n_bits) as u8;
+                    self.bits &= (1 << self.n_bits) - 1;
+                    n += 1;
+                }
+            }
+            self.inner.consume(used);
+        }
+        Ok(n)
+    }
+}
+
+/// Expands the run-length compression applied before encoding.
+struct Rle90Reader<R> {
+    inner: R,
+    last: u8,
+    repeat: u8,
+}
+
+impl <R: Read> Rle90Reader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            last: 0,
+            repeat: 0,
+        }
+    }
+    fn next(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8];
+        match self.inner.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+}
+
+impl <R: Read> Read for Rle90Reader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let mut n = 0;
+        while n < out.len() {
+            if self.repeat > 0 {
+                out[n] = self.last;
+                self.repeat -= 1;
+                n += 1;
+                continue;
+            }
+            let byte = match self.next()? {
+                Some(byte) => byte,
+                None => break,
+            };
+            if byte != RLE_MARKER {
+                self.last = byte;
+                out[n] = byte;
+                n += 1;
+                continue;
+            }
+            match self.next()? {
+                None => Err(io::ErrorKind::UnexpectedEof)?,
+                Some(0) => {
+                    self.last = RLE_MARKER;
+                    out[n] = RLE_MARKER;
+                    n += 1;
+                },
+                // the count includes the byte which was already produced.
+                Some(count) => self.repeat = count - 1,
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// Wraps the text of a BinHex file so that reading it yields the decoded
+/// header, forks and checksums.
+fn decoder<R: Read>(archive: R) -> error::Result<impl Read> {
+    let hqx = HqxReader::new(BufReader::new(archive))?;
+    Ok(Rle90Reader::new(BufReader::new(hqx)))
+}
+
+/// The decoded header of a BinHex file.
+struct BinHexHeader {
+    name: Vec<u8>,
+    info: BinHexInfo,
+}
+
+impl BinHexHeader {
+    fn read<R: Read>(mut reader: R) -> error::Result<Self> {
+        let truncated = |offset| move |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::TruncatedHeader { offset },
+            _ => e.into(),
+        };
+        let mut bytes = vec![0u8];
+        reader.read_exact(&mut bytes).map_err(truncated(0))?;
+        let name_len = bytes[0] as usize;
+        bytes.resize(1 + name_len + INFO_LEN, 0);
+        reader.read_exact(&mut bytes[1..]).map_err(truncated(1))?;
+        let mut crc = [0u8; 2];
+        reader.read_exact(&mut crc).map_err(truncated(bytes.len() as u64))?;
+        let (expected, found) = (CRC.checksum(&bytes), u16::from_be_bytes(crc));
+        if expected != found {
+            return Err(Error::ChecksumMismatch { id: None, expected, found });
+        }
+        let (_, info) = BinHexInfo::from_bytes((&bytes[1 + name_len..], 0))?;
+        if info.version != 0 {
+            return Err(Error::UnsupportedVersion { version: info.version as u32 });
+        }
+        bytes.truncate(1 + name_len);
+        bytes.remove(0);
+        Ok(Self { name: bytes, info })
+    }
+    /// How many bytes the header takes up in the decoded stream.
+    fn len(&self) -> u64 {
+        (1 + self.name.len() + INFO_LEN + 2) as u64
+    }
+    fn layout(&self) -> (Entry, Entry) {
+        let data = Entry {
+            id: EntryType::DataFork.into(),
+            offset: self.len(),
+            len: self.info.data_len as u64,
+        };
+        let rsrc = Entry {
+            id: EntryType::ResourceFork.into(),
+            offset: data.offset + data.len + 2,
+            len: self.info.rsrc_len as u64,
+        };
+        (data, rsrc)
+    }
+    fn builder(&self) -> ArchiveBuilder {
+        let mut builder = Archive::builder();
+        builder.format(Format::BinHex);
+        builder.name(Filename(self.name.clone()));
+        builder.finf(FinderInfo {
+            file_type: self.info.file_type,
+            creator: self.info.creator,
+            flags: self.info.flags,
+            location: Point::default(),
+            folder: Folder::default(),
+        });
+        builder
+    }
+}
+
+/// Copies a fork out of the decoded stream and checks it against the
+/// checksum which follows it.
+fn copy_fork<R: Read>(mut reader: R, entry: &Entry, mut sink: Option<&mut dyn Write>) -> error::Result<()> {
+    let mut digest = CRC.digest();
+    let mut remaining = entry.len;
+    let mut buf = [0u8; 8192];
+    while remaining > 0 {
+        let len = remaining.min(buf.len() as u64) as usize;
+        reader.read_exact(&mut buf[..len])
+            .map_err(|e| Error::from(e).within(entry))?;
+        digest.update(&buf[..len]);
+        if let Some(sink) = sink.as_mut() {
+            sink.write_all(&buf[..len])?;
+        }
+        remaining -= len as u64;
+    }
+    let mut crc = [0u8; 2];
+    reader.read_exact(&mut crc)
+        .map_err(|e| Error::from(e).within(entry))?;
+    let (expected, found) = (digest.finalize(), u16::from_be_bytes(crc));
+    if expected != found {
+        return Err(Error::ChecksumMismatch { id: Some(entry.id), expected, found });
+    }
+    Ok(())
+}
+
+/// Decodes a BinHex file, sending its forks to `handler` as they are found.
+pub fn parse<R: Read, H: Handler>(
+    archive: R,
+    handler: &mut H,
+) -> error::Result<Archive> {
+    let mut reader = decoder(archive)?;
+    let header = BinHexHeader::read(&mut reader)?;
+    let (data, rsrc) = header.layout();
+    for (fork, entry) in [(Fork::Data, data), (Fork::Rsrc, rsrc)] {
+        let mut sink = handler.sink(fork);
+        copy_fork(&mut reader, &entry, sink.as_deref_mut().map(|sink| sink as &mut dyn Write))?;
+    }
+    header.builder()
+        .build()
+        .ok_or(Error::Incomplete)
+}
+
+/// Decodes a BinHex file into memory so that its forks can be read in any
+/// order. Entries refer to positions in the decoded contents.
+pub fn parse_seekable<R: Read>(
+    archive: R,
+) -> error::Result<SeekableArchive<Cursor<Vec<u8>>>> {
+    let mut decoded = vec![];
+    decoder(archive)?.read_to_end(&mut decoded)?;
+    let header = BinHexHeader::read(decoded.as_slice())?;
+    let (data, rsrc) = header.layout();
+    let mut remaining = &decoded[header.len() as usize..];
+    for entry in [data, rsrc] {
+        copy_fork(&mut remaining, &entry, None)?;
+    }
+    let mut builder = SeekableArchive::builder(Cursor::new(decoded));
+    *builder.archive() = header.builder();
+    builder.data_fork(data);
+    builder.rsrc_fork(rsrc);
+    builder.table_entry(data);
+    builder.table_entry(rsrc);
+    builder.build()
+        .ok_or(Error::Incomplete)
+}
+
+/// Applies the 6-bit encoding, wrapping lines as it goes.
+struct HqxWriter<W> {
+    inner: W,
+    bits: u32,
+    n_bits: u8,
+    column: usize,
+}
+
+impl <W: Write> HqxWriter<W> {
+    fn new(mut inner: W) -> io::Result<Self> {
+        inner.write_all(BANNER)?;
+        inner.write_all(b" 4.0)\n:")?;
+        Ok(Self {
+            inner,
+            bits: 0,
+            n_bits: 0,
+            column: 1,
+        })
+    }
+    fn put(&mut self, c: u8) -> io::Result<()> {
+        if self.column == LINE_LEN {
+            self.inner.write_all(b"\n")?;
+            self.column = 0;
+        }
+        self.inner.write_all(&[c])?;
+        self.column += 1;
+        Ok(())
+    }
+    /// Encodes any leftover bits and closes the data with a colon.
+    fn finish(mut self) -> io::Result<W> {
+        if self.n_bits > 0 {
+            let value = (self.bits << (6 - self.n_bits)) & 0x3f;
+            self.put(ALPHABET[value as usize])?;
+        }
+        self.put(b':')?;
+        self.inner.write_all(b"\n")?;
+        Ok(self.inner)
+    }
+}
+
+impl <W: Write> Write for HqxWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            self.bits = (self.bits << 8) | *byte as u32;
+            self.n_bits += 8;
+            while self.n_bits >= 6 {
+                self.n_bits -= 6;
+                let value = (self.bits >> self.n_bits) & 0x3f;
+                self.put(ALPHABET[value as usize])?;
+            }
+            self.bits &= (1 << self.n_bits) - 1;
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Applies the run-length compression, holding on to each run until it
+/// ends.
+struct Rle90Writer<W> {
+    inner: W,
+    run: Option<(u8, u8)>,
+}
+
+impl <W: Write> Rle90Writer<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, run: None }
+    }
+    fn literal(&mut self, byte: u8) -> io::Result<()> {
+        if byte == RLE_MARKER {
+            self.inner.write_all(&[RLE_MARKER, 0])
+        } else {
+            self.inner.write_all(&[byte])
+        }
+    }
+    fn end_run(&mut self) -> io::Result<()> {
+        match self.run.take() {
+            None => {},
+            Some((byte, count @ 1..=2)) => {
+                for _ in 0..count {
+                    self.literal(byte)?;
+                }
+            },
+            Some((byte, count)) => {
+                self.literal(byte)?;
+                self.inner.write_all(&[RLE_MARKER, count])?;
+            },
+        }
+        Ok(())
+    }
+    fn finish(mut self) -> io::Result<W> {
+        self.end_run()?;
+        Ok(self.inner)
+    }
+}
+
+impl <W: Write> Write for Rle90Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for byte in buf {
+            match &mut self.run {
+                Some((last, count)) if last == byte && *count < u8::MAX => *count += 1,
+                _ => {
+                    self.end_run()?;
+                    self.run = Some((*byte, 1));
+                },
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Encodes an archive and its forks as a BinHex 4.0 file.
+pub struct BinHexWriter<'a> {
+    name: Vec<u8>,
+    info: BinHexInfo,
+    data_fork: Option<Box<dyn Read + 'a>>,
+    rsrc_fork: Option<Box<dyn Read + 'a>>,
+}
+
+impl <'a> BinHexWriter<'a> {
+    pub fn new(archive: &Archive) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let Filename(name) = archive.name()
+            .ok_or_else(|| invalid("BinHex files must have a name"))?;
+        if name.is_empty() || name.len() > 63 {
+            return Err(invalid("BinHex names must be between 1 and 63 bytes"));
+        }
+        let finf = match archive.finder_info() {
+            Some(finf) => finf,
+            None => FinderInfo::from_bytes((&[0u8; 16], 0))?.1,
+        };
+        Ok(Self {
+            name,
+            info: BinHexInfo {
+                version: 0,
+                file_type: finf.file_type,
+                creator: finf.creator,
+                flags: finf.flags,
+                data_len: 0,
+                rsrc_len: 0,
+            },
+            data_fork: None,
+            rsrc_fork: None,
+        })
+    }
+    pub fn data_fork<R: Read + 'a>(&mut self, fork: R, len: u32) -> &Self {
+        self.info.data_len = len;
+        self.data_fork = Some(Box::new(fork));
+        self
+    }
+    pub fn rsrc_fork<R: Read + 'a>(&mut self, fork: R, len: u32) -> &Self {
+        self.info.rsrc_len = len;
+        self.rsrc_fork = Some(Box::new(fork));
+        self
+    }
+    pub fn write<W: Write>(self, out: W) -> io::Result<W> {
+        let Self { name, info, data_fork, rsrc_fork } = self;
+        let mut out = Rle90Writer::new(HqxWriter::new(out)?);
+        let mut header = vec![name.len() as u8];
+        header.extend_from_slice(&name);
+        header.extend(info.to_bytes()?);
+        out.write_all(&header)?;
+        out.write_all(&CRC.checksum(&header).to_be_bytes())?;
+        let forks = [
+            (data_fork, info.data_len),
+            (rsrc_fork, info.rsrc_len),
+        ];
+        for (fork, len) in forks {
+            let mut digest = CRC.digest();
+            let mut fork = fork.unwrap_or_else(|| Box::new(io::empty()))
+                .take(len as u64);
+            let mut buf = [0u8; 8192];
+            let mut copied = 0;
+            loop {
+                let n = fork.read(&mut buf)?;
+                if n == 0 {
+                    break;
+                }
+                digest.update(&buf[..n]);
+                out.write_all(&buf[..n])?;
+                copied += n;
+            }
+            if copied < len as usize {
+                Err(io::ErrorKind::UnexpectedEof)?;
+            }
+            out.write_all(&digest.finalize().to_be_bytes())?;
+        }
+        out.finish()?.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MultiForkArchive;
+
+    /// Encoded independently of this module, with a literal 0x90, a run of
+    /// 300 bytes and a run of 0x90 in the data fork.
+    const VECTOR: &str = "(This file must be converted with BinHex 4.0)
+
+:\"e*PB@3J6@8!9%9B9(4dH(3\"!*!$!6X!N!-$)9a)DC!!G'KPFQ9\"N2p\"N#f3!*!
+%C@jNK#B!!3)6F`:
+";
+
+    fn vector_data() -> Vec<u8> {
+        let mut data = b"Hi\x90there".to_vec();
+        data.extend([b'A'; 300]);
+        data.extend([0x90; 4]);
+        data.extend(b"end");
+        data
+    }
+
+    fn read_forks(archive: &mut SeekableArchive<Cursor<Vec<u8>>>) -> (Vec<u8>, Vec<u8>) {
+        let (mut data, mut rsrc) = (vec![], vec![]);
+        archive.data_fork().unwrap().unwrap().read_to_end(&mut data).unwrap();
+        archive.rsrc_fork().unwrap().unwrap().read_to_end(&mut rsrc).unwrap();
+        (data, rsrc)
+    }
+
+    #[test]
+    fn alphabet_decodes_to_its_index() {
+        for (value, c) in ALPHABET.iter().enumerate() {
+            assert_eq!(decode_char(*c), Some(value as u8));
+        }
+        assert_eq!(decode_char(b'7'), None);
+    }
+
+    #[test]
+    fn decodes_known_vector() {
+        let mut archive = parse_seekable(VECTOR.as_bytes()).unwrap();
+        assert_eq!(archive.name().unwrap().as_bytes(), b"Read Me");
+        let finf = archive.finder_info().unwrap();
+        assert_eq!(finf.file_type, FileType::from(*b"TEXT"));
+        assert_eq!(finf.creator, Creator::from(*b"ttxt"));
+        let (data, rsrc) = read_forks(&mut archive);
+        assert_eq!(data, vector_data());
+        assert_eq!(rsrc, [0, 1, 2]);
+    }
+
+    #[test]
+    fn decodes_indented_data_without_banner() {
+        let data = &VECTOR[VECTOR.find(':').unwrap()..];
+        let text = format!("\r\n\n \t{}", data.replace('\n', "\n    "));
+        assert_eq!(Format::detect(text.as_bytes()), Some(Format::BinHex));
+        let mut archive = parse_seekable(text.as_bytes()).unwrap();
+        assert_eq!(archive.name().unwrap().as_bytes(), b"Read Me");
+        assert_eq!(read_forks(&mut archive), (vector_data(), vec![0, 1, 2]));
+    }
+
+    #[test]
+    fn detects_banner_only_at_line_start() {
+        assert!(detect(VECTOR.as_bytes()));
+        assert!(detect(format!("From: someone\r\n\r\n  {VECTOR}").as_bytes()));
+        assert!(!detect(format!("see {VECTOR}").as_bytes()));
+        // a banner after the data has started is part of something else
+        let data = &VECTOR[VECTOR.find(':').unwrap()..];
+        assert!(!detect(format!("text\n{data}\n{VECTOR}").as_bytes()));
+    }
+
+    #[test]
+    fn rejects_corrupt_fork() {
+        let corrupt = VECTOR.replacen("C@jN", "C@jP", 1);
+        assert!(matches!(
+            parse_seekable(corrupt.as_bytes()),
+            Err(Error::ChecksumMismatch { .. }),
+        ));
+    }
+
+    #[test]
+    fn round_trips_through_writer() {
+        let mut data = vector_data();
+        data.extend((0..5000u32).map(|i| (i * 7919 % 251) as u8));
+        let rsrc: Vec<u8> = [0x90; 600].into_iter().chain(0..=255).collect();
+
+        let mut builder = Archive::builder();
+        builder.format(Format::BinHex);
+        builder.name(Filename::from(b"Round Trip".to_vec()));
+        let mut finf = FinderInfo::from_bytes((&[0u8; 16], 0)).unwrap().1;
+        finf.file_type = FileType::from(*b"APPL");
+        finf.creator = Creator::from(*b"????");
+        builder.finf(finf);
+        let archive = builder.build().unwrap();
+
+        let mut writer = BinHexWriter::new(&archive).unwrap();
+        writer.data_fork(data.as_slice(), data.len() as u32);
+        writer.rsrc_fork(rsrc.as_slice(), rsrc.len() as u32);
+        let encoded = writer.write(vec![]).unwrap();
+
+        let text = std::str::from_utf8(&encoded).unwrap();
+        let body = &text[text.find(':').unwrap()..];
+        assert!(body.lines().count() > 1);
+        assert!(body.lines().all(|line| line.len() <= LINE_LEN));
+
+        let mut parsed = parse_seekable(encoded.as_slice()).unwrap();
+        assert_eq!(parsed.name().unwrap().as_bytes(), b"Round Trip");
+        assert_eq!(parsed.finder_info().unwrap().file_type, FileType::from(*b"APPL"));
+        assert_eq!(read_forks(&mut parsed), (data, rsrc));
+    }
+}
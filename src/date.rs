@@ -1,4 +1,7 @@
-use std::fmt;
+use std::{
+    fmt,
+    time::{Duration, SystemTime},
+};
 use derive_more::{From, Into};
 use time::OffsetDateTime;
 
@@ -7,15 +10,65 @@ use deku::prelude::*;
 /// UNIX timestamp for 2000-01-01T00:00:00Z
 pub const MAC_EPOCH: i64 = 9_4668_4800;
 
+/// Seconds between 1904-01-01T00:00:00Z, the epoch used by the classic Mac OS,
+/// and [`MAC_EPOCH`].
+const CLASSIC_EPOCH_OFFSET: i64 = 3_029_529_600;
+
 /// Mac file timestamp: the number of seconds before or after
 /// the start of the year 2000.
 #[derive(Default, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, From, Into)]
 pub struct Date(#[deku(endian = "big")] i32);
 
 impl Date {
+    /// The value AppleSingle uses for dates which are not known.
+    pub const UNKNOWN: Self = Self(i32::MIN);
+
     fn as_unix_timestamp(&self) -> i64 {
         self.0 as i64 + MAC_EPOCH
     }
+    /// Converts a classic Mac OS timestamp, counting seconds since the start
+    /// of 1904, where zero means the date is not known.
+    pub fn from_classic(timestamp: u32) -> Self {
+        if timestamp == 0 {
+            return Self::UNKNOWN;
+        }
+        (timestamp as i64 - CLASSIC_EPOCH_OFFSET).try_into()
+            .map(Self)
+            .unwrap_or(Self::UNKNOWN)
+    }
+    /// Converts a time from the file system, giving [`Self::UNKNOWN`] for
+    /// times beyond the range of the format.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let timestamp = match time.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(after) => after.as_secs() as i64,
+            Err(before) => -(before.duration().as_secs() as i64),
+        };
+        (timestamp - MAC_EPOCH).try_into()
+            .map(Self)
+            .unwrap_or(Self::UNKNOWN)
+    }
+    /// Converts this date into a time for the file system, unless it is not
+    /// known.
+    pub fn to_system_time(&self) -> Option<SystemTime> {
+        if *self == Self::UNKNOWN {
+            return None;
+        }
+        let timestamp = self.as_unix_timestamp();
+        let offset = Duration::from_secs(timestamp.unsigned_abs());
+        match timestamp {
+            0.. => SystemTime::UNIX_EPOCH.checked_add(offset),
+            _ => SystemTime::UNIX_EPOCH.checked_sub(offset),
+        }
+    }
+    /// Converts this date into a classic Mac OS timestamp, counting seconds
+    /// since the start of 1904.
+    pub fn to_classic(&self) -> u32 {
+        if *self == Self::UNKNOWN {
+            return 0;
+        }
+        (self.0 as i64 + CLASSIC_EPOCH_OFFSET).try_into()
+            .unwrap_or(0)
+    }
 }
 
 impl TryInto<OffsetDateTime> for &Date {
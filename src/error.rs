@@ -0,0 +1,184 @@
+use std::{
+    error,
+    fmt,
+    io,
+};
+
+use deku::DekuError;
+
+use super::{
+    Entry,
+    rsrc::ResType,
+};
+
+/// The ways in which reading an archive can fail.
+#[derive(Debug)]
+pub enum Error {
+    /// The file does not begin with the magic number of the expected format.
+    BadMagic {
+        found: u32,
+    },
+    /// The file uses a version of the format which is not understood.
+    UnsupportedVersion {
+        version: u32,
+    },
+    /// The file ended before the header or entry table was complete, with
+    /// `offset` pointing at the part which could not be read.
+    TruncatedHeader {
+        offset: u64,
+    },
+    /// The file is not in any format that could be recognized.
+    UnrecognizedFormat,
+    /// An entry extends past the end of the file.
+    EntryOutOfBounds {
+        id: u32,
+        offset: u64,
+        len: u64,
+    },
+    /// An entry starts before the end of the entry table or of an entry that
+    /// was already read, so it cannot be reached without seeking.
+    OverlappingEntries {
+        id: u32,
+        /// The entry which was read past this one's offset, or `None` if it
+        /// was the entry table.
+        previous: Option<u32>,
+        offset: u64,
+    },
+    /// An entry with a fixed size is too small to hold its contents.
+    InvalidEntryLength {
+        id: u32,
+        offset: u64,
+        expected: u64,
+        found: u64,
+    },
+    /// A metadata entry of no fixed size is longer than `limit`, the most
+    /// that is held in memory while streaming.
+    EntryTooLarge {
+        id: u32,
+        offset: u64,
+        len: u64,
+        limit: u64,
+    },
+    /// A checksum stored in the file does not match the data it covers,
+    /// which is the entry with the given ID or the header if there is none.
+    ChecksumMismatch {
+        id: Option<u32>,
+        expected: u16,
+        found: u16,
+    },
+    /// The map of a resource fork points outside of itself or the fork, at
+    /// the given offset within the fork.
+    InvalidResourceMap {
+        offset: u64,
+    },
+    /// A resource does not hold what its type says it should.
+    InvalidResource {
+        res_type: ResType,
+    },
+    /// The contents of an entry could not be decoded.
+    Decode(DekuError),
+    /// The archive did not contain enough information to describe it.
+    Incomplete,
+    /// The underlying reader failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic { found } => write!(f, "bad magic number {found:#010x}"),
+            Self::UnsupportedVersion { version } => write!(f, "unsupported version {version:#010x}"),
+            Self::TruncatedHeader { offset } => write!(f, "header truncated at offset {offset}"),
+            Self::UnrecognizedFormat => write!(f, "unrecognized format"),
+            Self::EntryOutOfBounds { id, offset, len } => write!(
+                f,
+                "entry {id} at offset {offset} with length {len} extends past the end of the file",
+            ),
+            Self::OverlappingEntries { id, previous: Some(previous), offset } => write!(
+                f,
+                "entry {id} at offset {offset} overlaps entry {previous}",
+            ),
+            Self::OverlappingEntries { id, previous: None, offset } => write!(
+                f,
+                "entry {id} at offset {offset} overlaps the entry table",
+            ),
+            Self::InvalidEntryLength { id, offset, expected, found } => write!(
+                f,
+                "entry {id} at offset {offset} has length {found}, expected {expected}",
+            ),
+            Self::EntryTooLarge { id, offset, len, limit } => write!(
+                f,
+                "entry {id} at offset {offset} has length {len}, more than the {limit} bytes allowed",
+            ),
+            Self::ChecksumMismatch { id: Some(id), expected, found } => write!(
+                f,
+                "entry {id} has checksum {found:#06x}, expected {expected:#06x}",
+            ),
+            Self::ChecksumMismatch { id: None, expected, found } => write!(
+                f,
+                "header has checksum {found:#06x}, expected {expected:#06x}",
+            ),
+            Self::InvalidResourceMap { offset } => write!(
+                f,
+                "invalid resource map at offset {offset}",
+            ),
+            Self::InvalidResource { res_type } => write!(
+                f,
+                "invalid '{res_type}' resource",
+            ),
+            Self::Decode(e) => write!(f, "{e}"),
+            Self::Incomplete => write!(f, "incomplete archive"),
+            Self::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error {
+    pub(crate) fn out_of_bounds(entry: &Entry) -> Self {
+        Self::EntryOutOfBounds {
+            id: entry.id,
+            offset: entry.offset,
+            len: entry.len,
+        }
+    }
+    /// Blames `entry` if the file ended while it was being read.
+    pub(crate) fn within(self, entry: &Entry) -> Self {
+        match self {
+            Self::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof => Self::out_of_bounds(entry),
+            e => e,
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Decode(e) => Some(e),
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<DekuError> for Error {
+    fn from(e: DekuError) -> Self {
+        Self::Decode(e)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use deku::prelude::*;
+
+/// File information used by ProDOS and GS/OS.
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProDOSInfo {
+    pub access: ProDOSAccess,
+    #[deku(endian = "big")]
+    pub file_type: u16,
+    #[deku(endian = "big")]
+    pub aux_type: u32,
+}
+
+/// The ProDOS access bits, which control what may be done to a file.
+#[derive(DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct ProDOSAccess {
+    #[deku(bits = "1", pad_bits_before = "8")]
+    pub destroy_enabled: bool,
+    #[deku(bits = "1")]
+    pub rename_enabled: bool,
+    #[deku(bits = "1", pad_bits_after = "2")]
+    pub backup_needed: bool,
+    #[deku(bits = "1")]
+    pub is_invisible: bool,
+    #[deku(bits = "1")]
+    pub write_enabled: bool,
+    #[deku(bits = "1")]
+    pub read_enabled: bool,
+}
+
+impl fmt::Display for ProDOSAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = vec![];
+        if self.read_enabled {
+            text.push("READ");
+        }
+        if self.write_enabled {
+            text.push("WRITE");
+        }
+        if self.is_invisible {
+            text.push("INVISIBLE");
+        }
+        if self.backup_needed {
+            text.push("BACKUP_NEEDED");
+        }
+        if self.rename_enabled {
+            text.push("RENAME");
+        }
+        if self.destroy_enabled {
+            text.push("DESTROY");
+        }
+        write!(f, "{}", text.join("|"))
+    }
+}
+
+impl fmt::Debug for ProDOSAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProDOSAccess({})", self)
+    }
+}
+
+/// The attribute bits of an MS-DOS directory entry.
+#[derive(DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct MSDOSInfo {
+    #[deku(bits = "1", pad_bits_before = "10")]
+    pub archive: bool,
+    #[deku(bits = "1")]
+    pub is_directory: bool,
+    #[deku(bits = "1")]
+    pub is_volume_label: bool,
+    #[deku(bits = "1")]
+    pub is_system: bool,
+    #[deku(bits = "1")]
+    pub is_hidden: bool,
+    #[deku(bits = "1")]
+    pub is_read_only: bool,
+}
+
+impl fmt::Display for MSDOSInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = vec![];
+        if self.is_read_only {
+            text.push("READ_ONLY");
+        }
+        if self.is_hidden {
+            text.push("HIDDEN");
+        }
+        if self.is_system {
+            text.push("SYSTEM");
+        }
+        if self.is_volume_label {
+            text.push("VOLUME_LABEL");
+        }
+        if self.is_directory {
+            text.push("DIRECTORY");
+        }
+        if self.archive {
+            text.push("ARCHIVE");
+        }
+        write!(f, "{}", text.join("|"))
+    }
+}
+
+impl fmt::Debug for MSDOSInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MSDOSInfo({})", self)
+    }
+}
+
+/// The 8.3 name an AppleShare server presents to MS-DOS clients.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AFPShortName(pub(crate) Vec<u8>);
+
+impl From<Vec<u8>> for AFPShortName {
+    fn from(name: Vec<u8>) -> Self {
+        Self(name)
+    }
+}
+
+impl AsRef<[u8]> for AFPShortName {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Debug for AFPShortName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AFPShortName({})", self)
+    }
+}
+impl fmt::Display for AFPShortName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match std::str::from_utf8(&self.0) {
+            Ok(s) => write!(f, "{:?}", s),
+            Err(_) => write!(f, "{:?}", &self.0),
+        }
+    }
+}
+
+/// The file attributes maintained by an AppleShare server.
+#[derive(DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct AFPInfo {
+    #[deku(bits = "1", pad_bits_before = "16", pad_bits_after = "4")]
+    pub set_clear: bool,
+    #[deku(bits = "1", pad_bits_after = "1")]
+    pub copy_protect: bool,
+    #[deku(bits = "1")]
+    pub delete_inhibit: bool,
+    #[deku(bits = "1")]
+    pub rename_inhibit: bool,
+    #[deku(bits = "1")]
+    pub backup_needed: bool,
+    #[deku(bits = "1")]
+    pub write_inhibit: bool,
+    #[deku(bits = "1")]
+    pub rsrc_already_open: bool,
+    #[deku(bits = "1")]
+    pub data_already_open: bool,
+    #[deku(bits = "1")]
+    pub is_system: bool,
+    #[deku(bits = "1")]
+    pub is_multi_user: bool,
+    #[deku(bits = "1")]
+    pub is_invisible: bool,
+}
+
+impl fmt::Display for AFPInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = vec![];
+        if self.is_invisible {
+            text.push("INVISIBLE");
+        }
+        if self.is_multi_user {
+            text.push("MULTI_USER");
+        }
+        if self.is_system {
+            text.push("SYSTEM");
+        }
+        if self.data_already_open {
+            text.push("DATA_OPEN");
+        }
+        if self.rsrc_already_open {
+            text.push("RSRC_OPEN");
+        }
+        if self.write_inhibit {
+            text.push("WRITE_INHIBIT");
+        }
+        if self.backup_needed {
+            text.push("BACKUP_NEEDED");
+        }
+        if self.rename_inhibit {
+            text.push("RENAME_INHIBIT");
+        }
+        if self.delete_inhibit {
+            text.push("DELETE_INHIBIT");
+        }
+        if self.copy_protect {
+            text.push("COPY_PROTECT");
+        }
+        if self.set_clear {
+            text.push("SET_CLEAR");
+        }
+        write!(f, "{}", text.join("|"))
+    }
+}
+
+impl fmt::Debug for AFPInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AFPInfo({})", self)
+    }
+}
+
+/// The ID an AppleShare server assigned to the directory containing a file.
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[deku(endian = "big")]
+pub struct AFPDirectoryID(pub u32);
@@ -17,9 +17,43 @@ pub struct FinderInfo {
     pub folder: Folder,
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+/// A four-character code, as used throughout the Mac OS to identify types.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub struct FourCC(ForeignFourCC);
 
+impl FourCC {
+    pub const fn new(code: [u8; 4]) -> Self {
+        Self(ForeignFourCC(code))
+    }
+    pub fn bytes(&self) -> [u8; 4] {
+        self.0.0
+    }
+}
+
+impl From<[u8; 4]> for FourCC {
+    fn from(code: [u8; 4]) -> Self {
+        Self::new(code)
+    }
+}
+
+impl From<FourCC> for [u8; 4] {
+    fn from(code: FourCC) -> Self {
+        code.bytes()
+    }
+}
+
+impl PartialOrd for FourCC {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FourCC {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.bytes().cmp(&other.bytes())
+    }
+}
+
 impl<'a, C> DekuRead<'a, C> for FourCC where C: Copy, u8: DekuRead<'a, C> {
     fn read(
         input: &'a BitSlice<u8, Msb0>,
@@ -54,13 +88,25 @@ impl fmt::Display for FourCC {
 }
 
 /// Mac File Type code
-#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, From, Into)]
 pub struct FileType(FourCC);
 
+impl From<[u8; 4]> for FileType {
+    fn from(code: [u8; 4]) -> Self {
+        Self(code.into())
+    }
+}
+
 /// Mac Creator code
-#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, From, Into)]
 pub struct Creator(FourCC);
 
+impl From<[u8; 4]> for Creator {
+    fn from(code: [u8; 4]) -> Self {
+        Self(code.into())
+    }
+}
+
 /// Various flags that are either manipulated by the Finder or influence the way
 /// the Finder will present the file.
 #[derive(DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
@@ -159,29 +205,116 @@ pub struct Point {
 pub struct Folder(#[deku(bits = "16")] u16);
 
 /// A bunch of extra information which is not very useful to the typical
-/// developer.
+/// developer. It makes up the second half of the 32-byte Finder info.
 #[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
 pub struct ExtendedFinderInfo {
+    #[deku(endian = "big")]
     pub icon_id: i16,
+    /// Unused by the classic Finder, but claimed by later versions of Mac OS.
+    pub reserved: [u8; 6],
     pub filename_script: FilenameScript,
+    pub extended_flags: u8,
+    #[deku(endian = "big")]
     pub comment_id: i16,
+    /// The ID of the folder the file was in before being moved to the desktop
+    /// or the trash.
+    #[deku(endian = "big")]
     pub put_away_from: i32,
 }
 
 /// The script used to display the filename. If unspecified, then the finder
 /// should use whatever the user currently is using.
-#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
 #[deku(type = "i8")]
 pub enum FilenameScript {
+    #[default]
     #[deku(id = "0")]
     Unspecified,
     #[deku(id_pat = "_")]
     Script(NonZeroI8),
 }
 
-impl Default for FilenameScript {
-    fn default() -> Self {
-        Self::Unspecified
+impl FilenameScript {
+    /// The script code, which is only valid if the high bit of the field has
+    /// been set.
+    pub fn script_code(&self) -> Option<u8> {
+        match self {
+            Self::Script(script) if script.get() < 0 => Some(script.get() as u8 & 0x7f),
+            _ => None,
+        }
+    }
+}
+
+impl From<u8> for FilenameScript {
+    fn from(script: u8) -> Self {
+        match NonZeroI8::new(script as i8) {
+            Some(script) => Self::Script(script),
+            None => Self::Unspecified,
+        }
+    }
+}
+
+impl From<FilenameScript> for u8 {
+    fn from(script: FilenameScript) -> Self {
+        match script {
+            FilenameScript::Unspecified => 0,
+            FilenameScript::Script(script) => script.get() as u8,
+        }
+    }
+}
+
+/// A rectangle in QuickDraw's coordinate system
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct Rect {
+    pub top: i16,
+    pub left: i16,
+    pub bottom: i16,
+    pub right: i16,
+}
+
+/// The folder counterpart of [`FinderInfo`], describing the window the Finder
+/// opens for the folder.
+#[derive(DekuRead, DekuWrite, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FolderInfo {
+    pub window: Rect,
+    pub flags: FinderFlags,
+    pub location: Point,
+    #[deku(endian = "big")]
+    pub view: i16,
+}
+
+impl FolderInfo {
+    /// Decodes Finder info which was read before it was known to belong to a
+    /// folder.
+    pub(crate) fn from_file_info(finf: &FinderInfo) -> Result<Self, DekuError> {
+        let (_, dinf) = Self::from_bytes((&finf.to_bytes()?, 0))?;
+        Ok(dinf)
+    }
+}
+
+/// The folder counterpart of [`ExtendedFinderInfo`].
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedFolderInfo {
+    /// How far the contents of the window have been scrolled.
+    pub scroll: Point,
+    /// Used by the Finder to keep track of the chain of open folders.
+    #[deku(endian = "big")]
+    pub open_chain: i32,
+    pub filename_script: FilenameScript,
+    pub extended_flags: u8,
+    #[deku(endian = "big")]
+    pub comment_id: i16,
+    #[deku(endian = "big")]
+    pub put_away_from: i32,
+}
+
+impl ExtendedFolderInfo {
+    /// Decodes extended Finder info which was read before it was known to
+    /// belong to a folder.
+    pub(crate) fn from_file_info(fxinf: &ExtendedFinderInfo) -> Result<Self, DekuError> {
+        let (_, dxinf) = Self::from_bytes((&fxinf.to_bytes()?, 0))?;
+        Ok(dxinf)
     }
 }
 
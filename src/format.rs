@@ -0,0 +1,175 @@
+use std::{
+    fmt,
+    io::{
+        self,
+        Cursor,
+        Seek,
+        SeekFrom,
+        prelude::*,
+    },
+};
+
+use super::{
+    archive::{
+        Archive,
+        SeekableArchive,
+    },
+    appledouble,
+    binhex,
+    applesingle::{
+        self,
+        APPLEDOUBLE_MAGIC,
+        APPLESINGLE_MAGIC,
+        Handler,
+    },
+    error::{self, Error},
+    macbinary,
+};
+
+/// How many bytes [`Format::detect`] needs to see to tell every format apart.
+/// Text formats may be preceded by mail or news headers, so this leaves room
+/// for them.
+pub const DETECT_LEN: usize = 4096;
+
+/// The kinds of archive that can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Format {
+    AppleSingle,
+    AppleDouble,
+    MacBinary(macbinary::Version),
+    BinHex,
+    /// A plain file with its resource fork and Finder info in extended
+    /// attributes. Like the other formats spread over the file system rather
+    /// than held in a stream, it is never detected.
+    ExtendedAttributes,
+    /// A plain file with its resource fork and Finder info in the `.rsrc`
+    /// and `.finf` folders next to it, as kept by Basilisk II and
+    /// SheepShaver.
+    Basilisk,
+    /// A plain file with its resource fork in a file of the same name with
+    /// `.rsrc` added, and no Finder info.
+    RsrcFile,
+    /// A file in a zip archive, with its resource fork and Finder info in an
+    /// AppleDouble header file under `__MACOSX/`, as the macOS Finder
+    /// compresses them. A zip archive holds many files, so it is never
+    /// detected either.
+    Zip,
+}
+
+impl Format {
+    /// Identifies an archive by the first bytes of its contents. Anything
+    /// shorter than [`DETECT_LEN`] may not be recognized, but only because it
+    /// is too short to tell.
+    pub fn detect(header: &[u8]) -> Option<Self> {
+        if let Some(magic) = header.get(..4) {
+            match u32::from_be_bytes(magic.try_into().ok()?) {
+                APPLESINGLE_MAGIC => return Some(Self::AppleSingle),
+                APPLEDOUBLE_MAGIC => return Some(Self::AppleDouble),
+                _ => {},
+            }
+        }
+        // MacBinary is checked first, since its header is binary and its
+        // checksum makes it far less likely to be mistaken for text than
+        // the other way around.
+        if let Some(version) = macbinary::detect(header) {
+            return Some(Self::MacBinary(version));
+        }
+        binhex::detect(header).then_some(Self::BinHex)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AppleSingle => write!(f, "AppleSingle"),
+            Self::AppleDouble => write!(f, "AppleDouble"),
+            Self::MacBinary(macbinary::Version::I) => write!(f, "MacBinary"),
+            Self::MacBinary(macbinary::Version::II) => write!(f, "MacBinary II"),
+            Self::MacBinary(macbinary::Version::III) => write!(f, "MacBinary III"),
+            Self::BinHex => write!(f, "BinHex 4.0"),
+            Self::ExtendedAttributes => write!(f, "extended attributes"),
+            Self::Basilisk => write!(f, "Basilisk II"),
+            Self::RsrcFile => write!(f, ".rsrc file"),
+            Self::Zip => write!(f, "zip file"),
+        }
+    }
+}
+
+/// Reads the first bytes of a stream, stopping early only at its end.
+fn peek<R: Read>(reader: R) -> io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(DETECT_LEN);
+    reader.take(DETECT_LEN as u64).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// What an opened archive reads its entries from: the file itself, or its
+/// decoded contents for formats which have to be decoded up front.
+pub enum Contents<R> {
+    File(R),
+    Decoded(Cursor<Vec<u8>>),
+}
+
+impl <R: Read> Read for Contents<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Decoded(contents) => contents.read(buf),
+        }
+    }
+}
+
+impl <S: Seek> Seek for Contents<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            Self::Decoded(contents) => contents.seek(pos),
+        }
+    }
+}
+
+/// Opens an archive of any recognized format. An AppleDouble header file is
+/// opened on its own, without its data file.
+pub fn open<R: Read + Seek>(mut archive: R) -> error::Result<SeekableArchive<Contents<R>>> {
+    let start = archive.stream_position()?;
+    let format = Format::detect(&peek(&mut archive)?)
+        .ok_or(Error::UnrecognizedFormat)?;
+    archive.seek(SeekFrom::Start(start))?;
+    let archive = Contents::File(archive);
+    match format {
+        Format::AppleSingle => applesingle::parse_seekable(archive),
+        Format::AppleDouble => applesingle::parse_seekable_archive(
+            archive,
+            APPLEDOUBLE_MAGIC,
+            Format::AppleDouble,
+        ),
+        Format::MacBinary(_) => macbinary::parse_seekable(archive),
+        Format::BinHex => {
+            let archive = binhex::parse_seekable(archive)?;
+            Ok(archive.map_file(Contents::Decoded))
+        },
+        Format::ExtendedAttributes
+        | Format::Basilisk
+        | Format::RsrcFile
+        | Format::Zip => Err(Error::UnrecognizedFormat),
+    }
+}
+
+/// Parses an archive of any recognized format from a stream, sending its
+/// forks to `handler` as they are found.
+pub fn parse<R: Read, H: Handler>(mut archive: R, handler: &mut H) -> error::Result<Archive> {
+    let header = peek(&mut archive)?;
+    let format = Format::detect(&header)
+        .ok_or(Error::UnrecognizedFormat)?;
+    let archive = Cursor::new(header).chain(archive);
+    match format {
+        Format::AppleSingle => applesingle::parse(archive, handler),
+        Format::AppleDouble => appledouble::parse(archive, handler),
+        Format::MacBinary(_) => macbinary::parse(archive, handler),
+        Format::BinHex => binhex::parse(archive, handler),
+        Format::ExtendedAttributes
+        | Format::Basilisk
+        | Format::RsrcFile
+        | Format::Zip => Err(Error::UnrecognizedFormat),
+    }
+}
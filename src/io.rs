@@ -12,18 +12,29 @@ pub(crate) struct CountingReader<R> {
     count: u64,
 }
 
+impl <R> CountingReader<R> {
+    /// The number of bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.count
+    }
+}
+
 impl <R: Read> CountingReader<R> {
     pub fn skip_to(&mut self, offset: u64) -> IOResult<u64> {
         let Self { count, .. } = self;
         if *count > offset {
             Err(ErrorKind::Unsupported)?;
         }
-        let diff = (offset - *count) as u64;
+        let diff = offset - *count;
         if diff < 1 {
             return Ok(0);
         }
         let mut take = self.take(diff);
-        copy(&mut take, &mut sink())
+        let skipped = copy(&mut take, &mut sink())?;
+        if skipped < diff {
+            Err(ErrorKind::UnexpectedEof)?;
+        }
+        Ok(skipped)
     }
 }
 
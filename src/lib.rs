@@ -1,28 +1,72 @@
+// deku's derive macros expand to code which trips this lint.
+#![allow(clippy::manual_div_ceil)]
+
 use std::{
     fmt,
-    io::{Read, Seek, Write, Result, SeekFrom},
+    io::{self as stdio, Read, Seek, Write, Result, SeekFrom},
 };
 
 pub(crate) mod io;
+mod error;
 mod finder;
+mod fileinfo;
 mod archive;
+mod format;
 mod date;
+mod loose;
+#[cfg(test)]
+mod testing;
 pub mod applesingle;
+pub mod appledouble;
+pub mod macbinary;
+pub mod binhex;
+pub mod rsrc;
+pub mod basilisk;
+pub mod tree;
+pub mod zip;
+#[cfg(unix)]
+pub mod xattr;
 
 pub use crate::archive::{
     Archive,
-    Format,
+    MultiForkArchive,
     SeekableArchive,
 };
 pub use crate::date::{Date, Dates};
+pub use crate::loose::LooseFile;
+pub use crate::error::Error;
+pub use crate::format::{
+    Contents,
+    DETECT_LEN,
+    Format,
+    open,
+    parse,
+};
+pub use crate::fileinfo::{
+    AFPDirectoryID,
+    AFPInfo,
+    AFPShortName,
+    MSDOSInfo,
+    ProDOSAccess,
+    ProDOSInfo,
+};
 pub use crate::finder::{
     FileType,
     Creator,
+    ExtendedFinderInfo,
+    ExtendedFolderInfo,
+    FilenameScript,
     FinderFlags,
     FinderInfo,
+    Folder,
+    FolderInfo,
+    FourCC,
     MacInfo,
+    Point,
+    Rect,
 };
 
+#[derive(Default)]
 pub struct Header {
     entries: Vec<Entry>,
 }
@@ -36,25 +80,46 @@ impl Header {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Entry {
     id: u32,
-    offset: u32,
-    len: u32,
+    offset: u64,
+    len: u64,
 }
 
 impl Entry {
+    /// The ID of the entry, as assigned by the AppleSingle specification.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    /// The position of the entry's contents within its archive.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    /// The length of the entry's contents, in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    /// Whether the entry has no contents, as an empty fork may.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    /// The kind of entry, if its ID is one defined by the AppleSingle
+    /// specification.
+    pub fn entry_type(&self) -> Option<applesingle::EntryType> {
+        self.id.try_into().ok()
+    }
     /// Applies the limit defined in this Entry to the given input stream. If
     /// your stream is seekable, you should use
     /// [`fixate()`][Entry::fixate] which will ensure that the resulting
     /// stream captures the exact region that the entry represents.
     pub fn limit<'a, R: Read + 'a>(&self, stream: R) -> Result<Box<dyn Read + 'a>> {
-        Ok(Box::new(stream.take(self.len as u64)))
+        Ok(Box::new(stream.take(self.len)))
     }
     /// Applies the boundaries defined in this Entry to the given seekable
     /// input stream. This method will seek to the offset contained in this
     /// structure and restrict the amount of readable bytes in the returned
     /// value to the amount of bytes in the entry.
     pub fn fixate<'a, R: Read + Seek + 'a>(&self, mut stream: R) -> Result<Box<dyn Read + 'a>> {
-        stream.seek(SeekFrom::Start(self.offset as u64))?;
-        Ok(Box::new(stream.take(self.len as u64)))
+        stream.seek(SeekFrom::Start(self.offset))?;
+        Ok(Box::new(stream.take(self.len)))
     }
 }
 
@@ -96,6 +161,18 @@ impl <'r, R: Read> ForkExt<'r, R> for R {
 #[derive(Clone)]
 pub struct Filename(Vec<u8>);
 
+impl Filename {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for Filename {
+    fn from(name: Vec<u8>) -> Self {
+        Self(name)
+    }
+}
+
 impl fmt::Debug for Filename {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "Filename({})", self)
@@ -131,6 +208,57 @@ impl fmt::Display for Comment {
 pub struct ArchiveWriter<W> {
     header: Header,
     file: W,
+    position: u64,
+}
+
+impl <W: Write> ArchiveWriter<W> {
+    pub(crate) fn new(header: Header, file: W) -> Self {
+        Self { header, file, position: 0 }
+    }
+    pub(crate) fn entries(&self) -> &[Entry] {
+        &self.header.entries
+    }
+    /// Copies the contents of the entry at `index` from `source`, failing if
+    /// `source` runs out before the entry has been filled.
+    pub(crate) fn write_entry<R: Read>(&mut self, index: usize, source: R) -> Result<()> {
+        let entry = self.header.entries[index];
+        let mut section = SectionWriter {
+            archive: self,
+            entry: &entry,
+            position: 0,
+        };
+        stdio::copy(&mut source.take(entry.len), &mut section)?;
+        if (section.position as u64) < entry.len {
+            Err(stdio::ErrorKind::UnexpectedEof)?;
+        }
+        Ok(())
+    }
+    /// Copies all of `source` to the end of the archive, updating the offset
+    /// and length of the entry at `index` to match.
+    pub(crate) fn append_entry<R: Read>(&mut self, index: usize, mut source: R) -> Result<()> {
+        let offset = self.position;
+        let len = stdio::copy(&mut source, self)?;
+        let entry = &mut self.header.entries[index];
+        entry.offset = offset;
+        entry.len = len;
+        Ok(())
+    }
+    pub(crate) fn into_inner(self) -> W {
+        self.file
+    }
+}
+
+impl <W: Write + Seek> ArchiveWriter<W> {
+    /// Overwrites bytes which were previously written `offset` bytes into the
+    /// archive, such as an entry table, then returns to the end of the archive.
+    pub(crate) fn rewrite(&mut self, offset: u64, bytes: &[u8]) -> Result<()> {
+        let end = self.file.stream_position()?;
+        let start = end - self.position;
+        self.file.seek(SeekFrom::Start(start + offset))?;
+        self.file.write_all(bytes)?;
+        self.file.seek(SeekFrom::Start(end))?;
+        Ok(())
+    }
 }
 
 impl <W: Write> Write for ArchiveWriter<W> {
@@ -138,7 +266,9 @@ impl <W: Write> Write for ArchiveWriter<W> {
         self.file.flush()
     }
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        self.file.write(buf)
+        let bytes = self.file.write(buf)?;
+        self.position += bytes as u64;
+        Ok(bytes)
     }
 }
 
@@ -148,7 +278,7 @@ struct SectionWriter<'a, W> {
     position: usize,
 }
 
-impl <'a, W: Write + Seek> Write for SectionWriter<'a, W> {
+impl <'a, W: Write> Write for SectionWriter<'a, W> {
     fn flush(&mut self) -> Result<()> {
         self.archive.flush()?;
         Ok(())
@@ -0,0 +1,164 @@
+//! Files whose resource fork and Finder info are kept apart from them on the
+//! file system, rather than in an archive.
+
+use std::{
+    fs::{File, Metadata},
+    io::{
+        self,
+        Cursor,
+        prelude::*,
+    },
+    path::Path,
+};
+
+use deku::prelude::*;
+
+use super::{
+    Archive,
+    Date,
+    Dates,
+    Entry,
+    ExtendedFinderInfo,
+    Filename,
+    FinderInfo,
+    MultiForkArchive,
+    SeekableArchive,
+    appledouble::AppleDoubleFile,
+    archive::ArchiveBuilder,
+    applesingle::EntryType,
+    error::{self, Error},
+    format::Format,
+    rsrc::to_mac_roman,
+};
+
+/// A file's resource fork, held in memory, followed by its data fork, viewed
+/// as one seekable stream.
+pub type LooseFile = AppleDoubleFile<Cursor<Vec<u8>>, File>;
+
+/// Encodes the name of a file on the host in Mac OS Roman, or leaves it as
+/// UTF-8 if it cannot be.
+pub(crate) fn mac_name(name: &str) -> Filename {
+    let name = to_mac_roman(name)
+        .unwrap_or_else(|| name.as_bytes().to_vec());
+    Filename::from(name)
+}
+
+/// Assembles an archive out of the file or folder at `path` and the resource
+/// fork and Finder info kept apart from it, [described](describe) by `path`.
+pub(crate) fn assemble(
+    path: &Path,
+    rsrc: Vec<u8>,
+    finder_info: Option<Vec<u8>>,
+    format: Format,
+) -> error::Result<SeekableArchive<LooseFile>> {
+    let data = File::open(path)?;
+    let metadata = data.metadata()?;
+    let rsrc_len = rsrc.len() as u64;
+    let file = AppleDoubleFile::new(Cursor::new(rsrc), data)?;
+
+    let mut builder = SeekableArchive::builder(file);
+    let archive = builder.archive();
+    archive.format(format);
+    describe(archive, path, &metadata);
+    if let Some(info) = finder_info {
+        let finf = info.get(..16).ok_or(Error::TruncatedHeader { offset: 0 })?;
+        archive.finf(FinderInfo::from_bytes((finf, 0))?.1);
+        if let Some(fxinf) = info.get(16..32) {
+            archive.fxinf(ExtendedFinderInfo::from_bytes((fxinf, 0))?.1);
+        }
+    }
+    let rsrc_fork = Entry {
+        id: EntryType::ResourceFork.into(),
+        offset: 0,
+        len: rsrc_len,
+    };
+    let data_fork = Entry {
+        id: EntryType::DataFork.into(),
+        offset: rsrc_len,
+        len: metadata.len(),
+    };
+    if rsrc_len > 0 {
+        builder.rsrc_fork(rsrc_fork);
+        builder.table_entry(rsrc_fork);
+    }
+    if !metadata.is_dir() {
+        builder.data_fork(data_fork);
+        builder.table_entry(data_fork);
+    }
+    let archive = builder.build()
+        .ok_or(Error::Incomplete)?;
+    if metadata.is_dir() {
+        archive.into_folder()
+    } else {
+        Ok(archive)
+    }
+}
+
+/// Names an archive after the file or folder at `path` and dates it with
+/// `metadata`, which was read from it.
+pub(crate) fn describe(archive: &mut ArchiveBuilder, path: &Path, metadata: &Metadata) {
+    if let Some(name) = path.file_name() {
+        archive.name(mac_name(&name.to_string_lossy()));
+    }
+    let date = |time: io::Result<_>| time.map_or(Date::UNKNOWN, Date::from_system_time);
+    archive.date(Dates {
+        create: date(metadata.created()),
+        modify: date(metadata.modified()),
+        backup: Date::UNKNOWN,
+        access: date(metadata.accessed()),
+    });
+}
+
+/// Copies the data fork of `archive` into `data`, then reads its resource
+/// fork, which is empty if it has none, and hands both it and `data` to
+/// `companions` to store alongside, together with whatever else of the
+/// archive they keep. Every way of taking an archive apart goes through
+/// here, so the forks are always read in the same order.
+pub(crate) fn write_forks<A, W, F>(
+    archive: &mut A,
+    mut data: W,
+    companions: F,
+) -> error::Result<()>
+where
+    A: MultiForkArchive + ?Sized,
+    W: Write,
+    F: FnOnce(W, &Archive, Vec<u8>) -> error::Result<()>,
+{
+    if let Some(mut fork) = archive.data_fork()? {
+        io::copy(&mut fork, &mut data)?;
+    }
+    let rsrc = read_rsrc_fork(archive)?;
+    companions(data, archive.archive(), rsrc)
+}
+
+/// Writes the data fork of `archive` to a new file at `path`, dated with the
+/// archive's modification date, and hands the resource fork to `companions`
+/// as [`write_forks`] does.
+pub(crate) fn extract<A, F>(
+    archive: &mut A,
+    path: &Path,
+    companions: F,
+) -> error::Result<()>
+where
+    A: MultiForkArchive + ?Sized,
+    F: FnOnce(&Archive, Vec<u8>) -> error::Result<()>,
+{
+    let modified = archive.dates().and_then(|dates| dates.modify.to_system_time());
+    write_forks(archive, File::create(path)?, |file, archive, rsrc| {
+        if let Some(modified) = modified {
+            file.set_modified(modified)?;
+        }
+        companions(archive, rsrc)
+    })
+}
+
+/// Reads the whole resource fork of `archive`, which is empty if it has none.
+pub(crate) fn read_rsrc_fork<A: MultiForkArchive + ?Sized>(
+    archive: &mut A,
+) -> error::Result<Vec<u8>> {
+    let mut rsrc = vec![];
+    if let Some(mut fork) = archive.rsrc_fork()? {
+        fork.read_to_end(&mut rsrc)?;
+    }
+    Ok(rsrc)
+}
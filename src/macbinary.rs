@@ -0,0 +1,651 @@
+use std::io::{
+    self,
+    Seek,
+    SeekFrom,
+    prelude::*,
+};
+
+use crc::{Crc, CRC_16_XMODEM};
+use deku::prelude::*;
+
+use super::{
+    Comment,
+    Creator,
+    Date,
+    Dates,
+    Entry,
+    ExtendedFinderInfo,
+    FileType,
+    Filename,
+    FinderFlags,
+    FinderInfo,
+    MacInfo,
+    archive::{
+        Archive,
+        SeekableArchive,
+    },
+    applesingle::{
+        EntryType,
+        Fork,
+        Handler,
+    },
+    error::{self, Error},
+    format::Format,
+    finder::{
+        Folder,
+        Point,
+    },
+    io::ReadExt as _,
+};
+
+const HEADER_LEN: u64 = 128;
+const SIGNATURE: [u8; 4] = *b"mBIN";
+/// The version a reader must understand to read MacBinary II and III files,
+/// which is the most recent one this reader does.
+const MIN_VERSION: u8 = 129;
+
+pub(crate) const CRC: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+/// The revision of the MacBinary format a file was written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Version {
+    I,
+    II,
+    III,
+}
+
+impl Version {
+    fn format(&self) -> Format {
+        Format::MacBinary(*self)
+    }
+}
+
+/// The 128-byte header at the beginning of every MacBinary file.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MacBinaryHeader {
+    pub old_version: u8,
+    pub name_len: u8,
+    pub name: [u8; 63],
+    pub file_type: FileType,
+    pub creator: Creator,
+    pub flags_high: u8,
+    pub zero_1: u8,
+    pub location: Point,
+    pub folder: Folder,
+    pub protected: u8,
+    pub zero_2: u8,
+    #[deku(endian = "big")]
+    pub data_len: u32,
+    #[deku(endian = "big")]
+    pub rsrc_len: u32,
+    #[deku(endian = "big")]
+    pub created: u32,
+    #[deku(endian = "big")]
+    pub modified: u32,
+    #[deku(endian = "big")]
+    pub comment_len: u16,
+    pub flags_low: u8,
+    pub signature: [u8; 4],
+    pub script: u8,
+    pub extended_flags: u8,
+    #[deku(endian = "big", pad_bytes_before = "8")]
+    pub unpacked_len: u32,
+    #[deku(endian = "big")]
+    pub secondary_header_len: u16,
+    pub version: u8,
+    pub min_version: u8,
+    #[deku(endian = "big", pad_bytes_after = "2")]
+    pub crc: u16,
+}
+
+impl MacBinaryHeader {
+    fn read<R: Read>(mut reader: R) -> error::Result<(Self, Version)> {
+        let mut bytes = [0u8; HEADER_LEN as usize];
+        reader.read_exact(&mut bytes).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::TruncatedHeader { offset: 0 },
+            _ => e.into(),
+        })?;
+        let version = detect(&bytes)
+            .ok_or(Error::UnrecognizedFormat)?;
+        if version >= Version::II {
+            let expected = CRC.checksum(&bytes[..124]);
+            let found = u16::from_be_bytes([bytes[124], bytes[125]]);
+            if expected != found {
+                return Err(Error::ChecksumMismatch { id: None, expected, found });
+            }
+        }
+        let (_, header) = Self::from_bytes((&bytes, 0))?;
+        Ok((header, version))
+    }
+    fn filename(&self) -> Filename {
+        let len = (self.name_len as usize).min(self.name.len());
+        Filename(self.name[..len].to_vec())
+    }
+    fn finder_info(&self, version: Version) -> error::Result<FinderInfo> {
+        let low = if version >= Version::II {
+            self.flags_low
+        } else {
+            0
+        };
+        let (_, flags) = FinderFlags::from_bytes((&[self.flags_high, low], 0))?;
+        Ok(FinderInfo {
+            file_type: self.file_type,
+            creator: self.creator,
+            flags,
+            location: self.location,
+            folder: self.folder,
+        })
+    }
+    /// Only MacBinary III carries any of the extended Finder info.
+    fn extended_finder_info(&self, version: Version) -> Option<ExtendedFinderInfo> {
+        if version < Version::III {
+            return None;
+        }
+        Some(ExtendedFinderInfo {
+            filename_script: self.script.into(),
+            extended_flags: self.extended_flags,
+            ..Default::default()
+        })
+    }
+    fn mac_info(&self) -> MacInfo {
+        MacInfo {
+            is_protected: self.protected & 1 != 0,
+            is_locked: false,
+        }
+    }
+    fn dates(&self) -> Dates {
+        Dates {
+            create: Date::from_classic(self.created),
+            modify: Date::from_classic(self.modified),
+            backup: Date::UNKNOWN,
+            access: Date::UNKNOWN,
+        }
+    }
+    fn layout(&self, version: Version) -> Layout {
+        let (secondary_header_len, comment_len) = if version >= Version::II {
+            (self.secondary_header_len as u64, self.comment_len as u64)
+        } else {
+            (0, 0)
+        };
+        let data_offset = HEADER_LEN + padded(secondary_header_len);
+        let rsrc_offset = data_offset + padded(self.data_len as u64);
+        let comment_offset = rsrc_offset + padded(self.rsrc_len as u64);
+        Layout {
+            data: Entry {
+                id: EntryType::DataFork.into(),
+                offset: data_offset,
+                len: self.data_len as u64,
+            },
+            rsrc: Entry {
+                id: EntryType::ResourceFork.into(),
+                offset: rsrc_offset,
+                len: self.rsrc_len as u64,
+            },
+            comment: Entry {
+                id: EntryType::Comment.into(),
+                offset: comment_offset,
+                len: comment_len,
+            },
+        }
+    }
+    fn builder(&self, version: Version) -> error::Result<crate::archive::ArchiveBuilder> {
+        let mut builder = Archive::builder();
+        builder.format(version.format());
+        builder.name(self.filename());
+        builder.finf(self.finder_info(version)?);
+        if let Some(fxinf) = self.extended_finder_info(version) {
+            builder.fxinf(fxinf);
+        }
+        builder.minf(self.mac_info());
+        builder.date(self.dates());
+        Ok(builder)
+    }
+}
+
+/// Where each part of a MacBinary file can be found.
+struct Layout {
+    data: Entry,
+    rsrc: Entry,
+    comment: Entry,
+}
+
+/// Rounds `len` up to the 128-byte blocks used by MacBinary.
+pub(crate) fn padded(len: u64) -> u64 {
+    len.div_ceil(HEADER_LEN) * HEADER_LEN
+}
+
+/// Determines which version of MacBinary, if any, the given header was
+/// written with. A header which claims to be MacBinary II or III is taken at
+/// its word even if its checksum is wrong, so that reading it reports the
+/// corruption rather than mistaking it for MacBinary I.
+pub fn detect(header: &[u8]) -> Option<Version> {
+    let header: &[u8; HEADER_LEN as usize] = header.get(..HEADER_LEN as usize)?
+        .try_into()
+        .ok()?;
+    if header[0] != 0 || header[74] != 0 || !(1..=63).contains(&header[1]) {
+        return None;
+    }
+    // a file which needs a newer reader than this one
+    if header[123] > MIN_VERSION {
+        return None;
+    }
+    let crc = u16::from_be_bytes([header[124], header[125]]);
+    let claims_ii = header[122] >= MIN_VERSION && header[123] == MIN_VERSION;
+    if CRC.checksum(&header[..124]) == crc || claims_ii {
+        if header[102..106] == SIGNATURE {
+            return Some(Version::III);
+        }
+        return Some(Version::II);
+    }
+    // MacBinary I has no checksum and leaves everything after the dates
+    // zeroed, so make sure of that and that the fork lengths are plausible
+    // before accepting it.
+    if header[82] != 0 || header[99..].iter().any(|b| *b != 0) {
+        return None;
+    }
+    let data_len = u32::from_be_bytes(header[83..87].try_into().ok()?);
+    let rsrc_len = u32::from_be_bytes(header[87..91].try_into().ok()?);
+    if data_len >= 0x7f_ffff || rsrc_len >= 0x7f_ffff {
+        return None;
+    }
+    Some(Version::I)
+}
+
+pub fn parse<R: Read, H: Handler>(
+    archive: R,
+    handler: &mut H,
+) -> error::Result<Archive> {
+    let mut reader = archive.counting();
+    let (header, version) = MacBinaryHeader::read(&mut reader)?;
+    let mut builder = header.builder(version)?;
+    let Layout { data, rsrc, comment } = header.layout(version);
+    for (fork, entry) in [(Fork::Data, data), (Fork::Rsrc, rsrc)] {
+        reader.skip_to(entry.offset)
+            .map_err(|e| Error::from(e).within(&entry))?;
+        let mut fork_reader = entry.limit(&mut reader)?;
+        let copied = if let Some(mut sink) = handler.sink(fork) {
+            io::copy(&mut fork_reader, &mut sink)?
+        } else {
+            io::copy(&mut fork_reader, &mut io::sink())?
+        };
+        if copied < entry.len {
+            return Err(Error::out_of_bounds(&entry));
+        }
+    }
+    if comment.len > 0 {
+        reader.skip_to(comment.offset)
+            .map_err(|e| Error::from(e).within(&comment))?;
+        let mut buf = vec![];
+        comment.limit(&mut reader)?.read_to_end(&mut buf)?;
+        if (buf.len() as u64) < comment.len {
+            return Err(Error::out_of_bounds(&comment));
+        }
+        builder.comment(Comment(buf));
+    }
+    builder.build()
+        .ok_or(Error::Incomplete)
+}
+
+pub fn parse_seekable<R: Read + Seek>(
+    mut archive: R,
+) -> error::Result<SeekableArchive<R>> {
+    let (header, version) = MacBinaryHeader::read(&mut archive)?;
+    let Layout { data, rsrc, comment } = header.layout(version);
+    let file_len = archive.seek(SeekFrom::End(0))?;
+    for entry in [data, rsrc, comment] {
+        if entry.offset + entry.len > file_len {
+            return Err(Error::out_of_bounds(&entry));
+        }
+    }
+    let mut builder = SeekableArchive::builder(archive);
+    *builder.archive() = header.builder(version)?;
+    builder.data_fork(data);
+    builder.rsrc_fork(rsrc);
+    builder.table_entry(data);
+    builder.table_entry(rsrc);
+    if comment.len > 0 {
+        builder.table_entry(comment);
+        let mut buf = vec![];
+        builder.entry(comment)?.read_to_end(&mut buf)?;
+        builder.archive().comment(Comment(buf));
+    }
+    builder.build()
+        .ok_or(Error::Incomplete)
+}
+
+/// Encodes an archive and its forks as a MacBinary file.
+pub struct MacBinaryWriter<'a> {
+    header: MacBinaryHeader,
+    version: Version,
+    secondary_header: Vec<u8>,
+    comment: Vec<u8>,
+    data_fork: Option<Box<dyn Read + 'a>>,
+    rsrc_fork: Option<Box<dyn Read + 'a>>,
+}
+
+impl <'a> MacBinaryWriter<'a> {
+    pub fn new(archive: &Archive, version: Version) -> io::Result<Self> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+        let Filename(filename) = archive.name()
+            .ok_or_else(|| invalid("MacBinary files must have a name"))?;
+        if filename.is_empty() || filename.len() > 63 {
+            return Err(invalid("MacBinary names must be between 1 and 63 bytes"));
+        }
+        let mut name = [0u8; 63];
+        name[..filename.len()].copy_from_slice(&filename);
+        let finf = match archive.finder_info() {
+            Some(finf) => finf,
+            None => FinderInfo::from_bytes((&[0u8; 16], 0))?.1,
+        };
+        // MacBinary II has these cleared, since they only make sense on the
+        // disk the file came from. The changed flag is never kept at all.
+        let mut flags = finf.flags;
+        #[allow(deprecated)]
+        {
+            flags.is_on_desktop = false;
+        }
+        flags.has_been_inited = false;
+        let flags = flags.to_bytes()?;
+        let fxinf = match version {
+            Version::III => archive.extended_finder_info().unwrap_or_default(),
+            _ => ExtendedFinderInfo::default(),
+        };
+        let dates = archive.dates().unwrap_or(Dates {
+            create: Date::UNKNOWN,
+            modify: Date::UNKNOWN,
+            backup: Date::UNKNOWN,
+            access: Date::UNKNOWN,
+        });
+        let comment = match (version, archive.comment()) {
+            (Version::I, _) | (_, None) => vec![],
+            (_, Some(Comment(comment))) => comment,
+        };
+        let header = MacBinaryHeader {
+            old_version: 0,
+            name_len: filename.len() as u8,
+            name,
+            file_type: finf.file_type,
+            creator: finf.creator,
+            flags_high: flags[0],
+            zero_1: 0,
+            location: finf.location,
+            folder: finf.folder,
+            protected: archive.mac_info()
+                .map(|minf| minf.is_protected as u8)
+                .unwrap_or(0),
+            zero_2: 0,
+            data_len: 0,
+            rsrc_len: 0,
+            created: dates.create.to_classic(),
+            modified: dates.modify.to_classic(),
+            comment_len: comment.len().try_into()
+                .map_err(|_| invalid("comment is too long"))?,
+            flags_low: if version >= Version::II { flags[1] } else { 0 },
+            signature: if version >= Version::III { SIGNATURE } else { [0; 4] },
+            script: fxinf.filename_script.into(),
+            extended_flags: fxinf.extended_flags,
+            unpacked_len: 0,
+            secondary_header_len: 0,
+            version: match version {
+                Version::I => 0,
+                Version::II => 129,
+                Version::III => 130,
+            },
+            min_version: if version >= Version::II { MIN_VERSION } else { 0 },
+            crc: 0,
+        };
+        Ok(Self {
+            header,
+            version,
+            secondary_header: vec![],
+            comment,
+            data_fork: None,
+            rsrc_fork: None,
+        })
+    }
+    pub fn data_fork<R: Read + 'a>(&mut self, fork: R, len: u32) -> &Self {
+        self.header.data_len = len;
+        self.data_fork = Some(Box::new(fork));
+        self
+    }
+    pub fn rsrc_fork<R: Read + 'a>(&mut self, fork: R, len: u32) -> &Self {
+        self.header.rsrc_len = len;
+        self.rsrc_fork = Some(Box::new(fork));
+        self
+    }
+    /// Sets the secondary header which is placed between the header and the
+    /// data fork. It is ignored when writing MacBinary I.
+    pub fn secondary_header(&mut self, secondary_header: Vec<u8>) -> io::Result<&Self> {
+        self.header.secondary_header_len = secondary_header.len().try_into()
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "secondary header is too long"))?;
+        self.secondary_header = secondary_header;
+        Ok(self)
+    }
+    pub fn write<W: Write>(self, mut out: W) -> io::Result<W> {
+        let Self {
+            mut header,
+            version,
+            mut secondary_header,
+            comment,
+            data_fork,
+            rsrc_fork,
+        } = self;
+        if version == Version::I {
+            header.secondary_header_len = 0;
+            secondary_header.clear();
+        }
+        let mut bytes = header.to_bytes()?;
+        if version >= Version::II {
+            let crc = CRC.checksum(&bytes[..124]);
+            bytes[124..126].copy_from_slice(&crc.to_be_bytes());
+        }
+        out.write_all(&bytes)?;
+        write_padded(&mut out, &secondary_header[..], secondary_header.len() as u64)?;
+        let forks = [
+            (data_fork, header.data_len),
+            (rsrc_fork, header.rsrc_len),
+        ];
+        for (fork, len) in forks {
+            match fork {
+                Some(fork) => write_padded(&mut out, fork, len as u64)?,
+                None => write_padded(&mut out, io::empty(), len as u64)?,
+            }
+        }
+        write_padded(&mut out, &comment[..], comment.len() as u64)?;
+        Ok(out)
+    }
+}
+
+/// Copies exactly `len` bytes from `source`, followed by enough zeroes to
+/// reach the next 128-byte boundary.
+fn write_padded<R: Read, W: Write>(out: &mut W, source: R, len: u64) -> io::Result<()> {
+    let copied = io::copy(&mut source.take(len), out)?;
+    if copied < len {
+        Err(io::ErrorKind::UnexpectedEof)?;
+    }
+    io::copy(&mut io::repeat(0).take(padded(len) - len), out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MultiForkArchive;
+
+    /// A MacBinary II header for "Read Me", with its checksum computed
+    /// independently of this module. The data fork is 5 bytes long, the
+    /// resource fork 3 and the comment 4.
+    const HEADER: [u8; 128] = [
+        0x00, 0x07, 0x52, 0x65, 0x61, 0x64, 0x20, 0x4d, 0x65, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x54, 0x45, 0x58, 0x54, 0x74, 0x74, 0x78, 0x74, 0x20, 0x00, 0x00, 0x10, 0x00, 0x20, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x03, 0xb0, 0x00, 0x00, 0x00, 0xb0,
+        0x00, 0x01, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x81, 0x81, 0xe8, 0x63, 0x00, 0x00,
+    ];
+
+    /// The header followed by its forks and comment, each padded to a
+    /// whole block.
+    fn vector() -> Vec<u8> {
+        let mut file = HEADER.to_vec();
+        for part in [&b"hello"[..], b"rsr", b"note"] {
+            let start = file.len();
+            file.extend_from_slice(part);
+            file.resize(start + HEADER_LEN as usize, 0);
+        }
+        file
+    }
+
+    /// Replaces the checksum of a header after changing it.
+    fn recompute_crc(header: &mut [u8]) {
+        let crc = CRC.checksum(&header[..124]);
+        header[124..126].copy_from_slice(&crc.to_be_bytes());
+    }
+
+    #[test]
+    fn crc_matches_xmodem() {
+        assert_eq!(CRC.checksum(b"123456789"), 0x31c3);
+    }
+
+    #[test]
+    fn decodes_known_header() {
+        let mut archive = parse_seekable(io::Cursor::new(vector())).unwrap();
+        assert_eq!(archive.format(), Format::MacBinary(Version::II));
+        assert_eq!(archive.name().unwrap().as_bytes(), b"Read Me");
+        let finf = archive.finder_info().unwrap();
+        assert_eq!(finf.file_type, FileType::from(*b"TEXT"));
+        assert_eq!(finf.creator, Creator::from(*b"ttxt"));
+        assert!(finf.flags.has_bundle);
+        assert_eq!(finf.location, Point { vertical: 16, horizontal: 32 });
+        let dates = archive.dates().unwrap();
+        assert_eq!(dates.create, Date::from_classic(0xb000_0000));
+        assert_eq!(dates.modify, Date::from_classic(0xb000_0100));
+        assert_eq!(archive.comment().unwrap().0, b"note");
+
+        let (mut data, mut rsrc) = (vec![], vec![]);
+        archive.data_fork().unwrap().unwrap().read_to_end(&mut data).unwrap();
+        archive.rsrc_fork().unwrap().unwrap().read_to_end(&mut rsrc).unwrap();
+        assert_eq!((data.as_slice(), rsrc.as_slice()), (&b"hello"[..], &b"rsr"[..]));
+    }
+
+    #[test]
+    fn detects_each_version() {
+        assert_eq!(detect(&HEADER), Some(Version::II));
+
+        let mut iii = HEADER;
+        iii[102..106].copy_from_slice(&SIGNATURE);
+        iii[122] = 130;
+        recompute_crc(&mut iii);
+        assert_eq!(detect(&iii), Some(Version::III));
+
+        // everything MacBinary II added is zeroed
+        let mut i = HEADER;
+        i[99..].fill(0);
+        assert_eq!(detect(&i), Some(Version::I));
+    }
+
+    #[test]
+    fn detects_macbinary_holding_binhex_banner() {
+        let archive = parse_seekable(io::Cursor::new(vector())).unwrap().into_archive();
+        let data = b"\n(This file must be converted with BinHex 4.0)\n";
+        let mut writer = MacBinaryWriter::new(&archive, Version::II).unwrap();
+        writer.data_fork(&data[..], data.len() as u32);
+        let file = writer.write(vec![]).unwrap();
+        assert_eq!(Format::detect(&file), Some(Format::MacBinary(Version::II)));
+
+        let mut archive = crate::open(io::Cursor::new(file)).unwrap();
+        let mut fork = vec![];
+        archive.data_fork().unwrap().unwrap().read_to_end(&mut fork).unwrap();
+        assert_eq!(fork, data);
+    }
+
+    #[test]
+    fn rejects_implausible_macbinary_i() {
+        let mut i = HEADER;
+        i[99..].fill(0);
+        for (offset, value) in [(74, 1), (82, 1), (110, 1), (83, 0x7f)] {
+            let mut header = i;
+            header[offset] = value;
+            assert_eq!(detect(&header), None, "byte {offset} set to {value:#x}");
+        }
+        assert_eq!(detect(&[0; 128]), None);
+    }
+
+    #[test]
+    fn reports_corrupt_macbinary_ii() {
+        let mut file = vector();
+        file[2] = b'r';
+        assert_eq!(detect(&file), Some(Version::II));
+        let e = parse_seekable(io::Cursor::new(file)).unwrap_err();
+        assert!(matches!(e, Error::ChecksumMismatch { id: None, found: 0xe863, .. }), "{e:?}");
+    }
+
+    #[test]
+    fn writes_known_header() {
+        let archive = parse_seekable(io::Cursor::new(vector())).unwrap();
+        let mut finf = archive.finder_info().unwrap();
+        finf.flags.has_been_inited = true;
+        #[allow(deprecated)]
+        {
+            finf.flags.is_on_desktop = true;
+        }
+        let mut builder = Archive::builder();
+        builder.format(Format::MacBinary(Version::II));
+        builder.name(archive.name().unwrap());
+        builder.finf(finf);
+        builder.date(archive.dates().unwrap());
+        builder.comment(archive.comment().unwrap());
+
+        let mut writer = MacBinaryWriter::new(&builder.build().unwrap(), Version::II).unwrap();
+        writer.data_fork(&b"hello"[..], 5);
+        writer.rsrc_fork(&b"rsr"[..], 3);
+        assert_eq!(writer.write(vec![]).unwrap(), vector());
+    }
+
+    #[test]
+    fn round_trips_macbinary_iii() {
+        let original = parse_seekable(io::Cursor::new(vector())).unwrap().into_archive();
+        let mut builder = Archive::builder();
+        builder.format(Format::MacBinary(Version::III));
+        builder.name(original.name().unwrap());
+        builder.finf(original.finder_info().unwrap());
+        builder.fxinf(ExtendedFinderInfo {
+            filename_script: 1.into(),
+            extended_flags: 0x80,
+            ..Default::default()
+        });
+        builder.date(original.dates().unwrap());
+        builder.comment(original.comment().unwrap());
+        let original = builder.build().unwrap();
+        let data: Vec<u8> = (0..300u16).map(|i| i as u8).collect();
+
+        let mut writer = MacBinaryWriter::new(&original, Version::III).unwrap();
+        writer.data_fork(data.as_slice(), data.len() as u32);
+        writer.secondary_header(vec![0xaa; 10]).unwrap();
+        let file = writer.write(vec![]).unwrap();
+        // header, secondary header, data fork, empty resource fork, comment
+        assert_eq!(file.len(), 128 + 128 + 384 + 128);
+
+        let mut archive = parse_seekable(io::Cursor::new(file)).unwrap();
+        assert_eq!(archive.format(), Format::MacBinary(Version::III));
+        assert_eq!(archive.name().unwrap().as_bytes(), b"Read Me");
+        assert_eq!(archive.finder_info(), original.finder_info());
+        assert_eq!(archive.extended_finder_info(), original.extended_finder_info());
+        assert_eq!(archive.dates().unwrap().modify, original.dates().unwrap().modify);
+        assert_eq!(archive.comment().unwrap().0, b"note");
+        let mut fork = vec![];
+        archive.data_fork().unwrap().unwrap().read_to_end(&mut fork).unwrap();
+        assert_eq!(fork, data);
+    }
+
+    #[test]
+    fn rejects_newer_min_version() {
+        let mut header = HEADER;
+        header[122] = 131;
+        header[123] = 130;
+        recompute_crc(&mut header);
+        assert_eq!(detect(&header), None);
+    }
+}
@@ -1,18 +1,42 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use clio::Input;
-use std::io::{Seek, Write};
+use std::{
+    io::{Seek, Write},
+    path::PathBuf,
+};
 
 use console::style;
 
 use forkcordion::{
-    Archive,
-    Format,
-    SeekableArchive,
+    MultiForkArchive,
+    appledouble,
     applesingle::{self, Fork},
+    tree::{self, Outcome, Representation},
 };
 
 #[derive(Parser, Debug)]
-#[clap(name = "applesingle-info", author, version, about)]
+#[clap(name = "forkcordion", author, version, about)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Describe an archive of any supported format and extract its forks
+    Info(InfoCommand),
+    /// Report every way in which an AppleSingle or AppleDouble file departs
+    /// from the specification
+    Validate(ValidateCommand),
+    /// Extract every archive in a folder into another folder, keeping the
+    /// forks in the chosen representation
+    Unpack(TreeCommand),
+    /// Pack every file in a folder, along with the forks kept beside it,
+    /// into AppleSingle files in another folder
+    Pack(TreeCommand),
+}
+
+#[derive(Args, Debug)]
 struct InfoCommand {
     #[clap(value_parser, default_value = "-")]
     input: Input,
@@ -24,9 +48,54 @@ struct InfoCommand {
 
 impl InfoCommand {
     fn seekable(&mut self) -> bool {
-        match self.input.seek(std::io::SeekFrom::Current(0)) {
-            Ok(_) => true,
-            Err(_) => false,
+        self.input.stream_position().is_ok()
+    }
+}
+
+#[derive(Args, Debug)]
+struct ValidateCommand {
+    #[clap(value_parser, default_value = "-")]
+    input: Input,
+    /// Expect an AppleDouble header file rather than an AppleSingle file
+    #[clap(long)]
+    appledouble: bool,
+}
+
+#[derive(Args, Debug)]
+struct TreeCommand {
+    #[clap(value_parser)]
+    source: PathBuf,
+    #[clap(value_parser)]
+    target: PathBuf,
+    /// Where the resource fork and Finder info of each file are kept
+    #[clap(long, value_enum, default_value_t = RepresentationArg::Appledouble)]
+    representation: RepresentationArg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum RepresentationArg {
+    /// Extended attributes on the file itself
+    #[cfg(unix)]
+    Xattr,
+    /// A `._` AppleDouble header file next to the file
+    Appledouble,
+    /// An AppleDouble header file in a `.AppleDouble` folder
+    Netatalk,
+    /// The resource fork alone, in a `.rsrc` file next to the file
+    Rsrc,
+    /// Basilisk II's `.rsrc` and `.finf` folders
+    Basilisk,
+}
+
+impl From<RepresentationArg> for Representation {
+    fn from(arg: RepresentationArg) -> Self {
+        match arg {
+            #[cfg(unix)]
+            RepresentationArg::Xattr => Self::ExtendedAttributes,
+            RepresentationArg::Appledouble => Self::AppleDouble(appledouble::Layout::DotUnderscore),
+            RepresentationArg::Netatalk => Self::AppleDouble(appledouble::Layout::Netatalk),
+            RepresentationArg::Rsrc => Self::RsrcFile,
+            RepresentationArg::Basilisk => Self::Basilisk,
         }
     }
 }
@@ -64,35 +133,83 @@ impl applesingle::Handler for Handler {
     }
 }
 
-enum ArchiveKind<R> {
-    Seekable(SeekableArchive<R>),
-    Streaming(Archive),
+fn main() {
+    match Cli::parse().command {
+        Command::Info(cmd) => info(cmd),
+        Command::Validate(cmd) => validate(cmd),
+        Command::Unpack(cmd) => walk_tree(cmd, "unpacking", tree::unpack),
+        Command::Pack(cmd) => walk_tree(cmd, "packing", tree::pack),
+    }
 }
 
-impl <R: std::io::Read + std::io::Seek> ArchiveKind<R> {
-    fn format(&self) -> Format {
-        match self {
-            Self::Seekable(a) => a.format(),
-            Self::Streaming(a) => a.format(),
-        }
-    }
-    fn name(&self) -> Option<forkcordion::Filename> {
-        match self {
-            Self::Seekable(a) => a.name(),
-            Self::Streaming(a) => a.name(),
+fn walk_tree(
+    cmd: TreeCommand,
+    verb: &str,
+    operation: fn(PathBuf, PathBuf, Representation) -> std::io::Result<Vec<Outcome>>,
+) {
+    let TreeCommand { source, target, representation } = cmd;
+
+    eprintln!(
+        "{verb} {:?} into {:?}",
+        style(&source).yellow(),
+        style(&target).yellow(),
+    );
+
+    let outcomes = match operation(source, target, representation.into()) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            eprintln!("{}", style(e).red());
+            std::process::exit(2);
+        },
+    };
+
+    let mut failed = false;
+    for Outcome { source, result } in outcomes {
+        match result {
+            Ok(path) => println!("{} -> {}", source.display(), style(path.display()).green()),
+            Err(e) => {
+                println!("{}: {}", source.display(), style(e).red());
+                failed = true;
+            },
         }
     }
-    fn finder_info(&self) -> Option<forkcordion::FinderInfo> {
-        match self {
-            Self::Seekable(a) => a.finder_info(),
-            Self::Streaming(a) => a.finder_info(),
-        }
+    if failed {
+        std::process::exit(1);
     }
 }
 
-fn main() {
+fn validate(cmd: ValidateCommand) {
+    let ValidateCommand { input, appledouble } = cmd;
+
+    eprintln!(
+        "validating {:?}",
+        style(&input).yellow(),
+    );
+
+    let violations = if appledouble {
+        appledouble::validate(input)
+    } else {
+        applesingle::validate(input)
+    };
+    let violations = match violations {
+        Ok(violations) => violations,
+        Err(e) => {
+            eprintln!("{}", style(e).red());
+            std::process::exit(2);
+        },
+    };
 
-    let mut cmd = InfoCommand::parse();
+    for violation in &violations {
+        println!("{violation}");
+    }
+    if violations.is_empty() {
+        eprintln!("{}", style("no violations found").green());
+    } else {
+        std::process::exit(1);
+    }
+}
+
+fn info(mut cmd: InfoCommand) {
     let seekable = cmd.seekable();
     let InfoCommand { input, mut output_rsrc, mut output_data } = cmd;
 
@@ -102,7 +219,7 @@ fn main() {
     );
 
     let archive = if seekable {
-        let mut archive = applesingle::parse_seekable(input)
+        let mut archive = forkcordion::open(input)
             .expect("failed to parse seekable archive");
         if let (Some(out), Ok(Some(mut fork))) = (&mut output_data, archive.data_fork()) {
             std::io::copy(&mut fork, out)
@@ -112,12 +229,21 @@ fn main() {
             std::io::copy(&mut fork, out)
                 .expect("failed to export rsrc fork");
         }
-        ArchiveKind::Seekable(archive)
+        for entry in archive.entries() {
+            eprintln!(
+                "entry id={} type={:?} offset={} len={}",
+                entry.id(),
+                entry.entry_type(),
+                entry.offset(),
+                entry.len(),
+            );
+        }
+        Box::new(archive) as Box<dyn MultiForkArchive>
     } else {
         let mut h = Handler { output_rsrc, output_data } ;
-        let archive = applesingle::parse(input, &mut h)
+        let archive = forkcordion::parse(input, &mut h)
             .expect("failed to parse streaming archive");
-        ArchiveKind::Streaming(archive)
+        Box::new(archive)
     };
 
     eprintln!("format={}", style(archive.format()).cyan());
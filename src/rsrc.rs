@@ -0,0 +1,751 @@
+//! Access to the resources stored in a resource fork.
+
+use std::{
+    fmt,
+    io::{
+        self,
+        Cursor,
+        Seek,
+        SeekFrom,
+        prelude::*,
+    },
+};
+
+use derive_more::{From, Into};
+use deku::prelude::*;
+
+use super::{
+    FourCC,
+    MultiForkArchive,
+    ResourceFork,
+    error::{self, Error},
+    loose,
+};
+
+mod bundle;
+mod icon;
+mod text;
+
+pub use bundle::{
+    Bundle,
+    BundleResources,
+    BundleSummary,
+    ClaimedType,
+    FREF,
+    FileReference,
+    LocalId,
+    find_bundle,
+};
+pub use icon::{
+    CICN,
+    ICNS,
+    IconFamily,
+    Image,
+    archive_icon,
+    render_color_icon,
+};
+pub use text::{
+    FontStyle,
+    PascalString,
+    ReleaseStage,
+    RgbColor,
+    StringList,
+    StyleRun,
+    Styles,
+    Text,
+    Version,
+    mac_roman,
+    to_mac_roman,
+};
+
+pub(crate) const HEADER_LEN: usize = 16;
+pub(crate) const MAP_HEADER_LEN: usize = 28;
+pub(crate) const TYPE_LEN: usize = 8;
+pub(crate) const REFERENCE_LEN: usize = 12;
+/// Marks a resource which has no name.
+pub(crate) const NO_NAME: u16 = 0xffff;
+
+/// The type of a resource, such as `STR#` or `ICN#`.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, From, Into)]
+pub struct ResType(FourCC);
+
+impl ResType {
+    pub const fn new(code: [u8; 4]) -> Self {
+        Self(FourCC::new(code))
+    }
+}
+
+impl From<[u8; 4]> for ResType {
+    fn from(code: [u8; 4]) -> Self {
+        Self::new(code)
+    }
+}
+
+impl fmt::Display for ResType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A kind of resource whose contents can be decoded.
+pub trait ResourceData: Sized {
+    /// The type of resource which holds this kind of data.
+    const TYPE: ResType;
+    fn decode(data: &[u8]) -> error::Result<Self>;
+    fn encode(&self) -> error::Result<Vec<u8>>;
+}
+
+/// The header at the start of a resource fork, which is repeated at the
+/// start of the map.
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub(crate) struct ResourceHeader {
+    pub data_offset: u32,
+    pub map_offset: u32,
+    pub data_len: u32,
+    pub map_len: u32,
+}
+
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MapHeader {
+    pub header: ResourceHeader,
+    #[deku(endian = "big")]
+    pub next_map: u32,
+    #[deku(endian = "big")]
+    pub file_ref: u16,
+    pub attributes: MapAttributes,
+    #[deku(endian = "big")]
+    pub type_list_offset: u16,
+    #[deku(endian = "big")]
+    pub name_list_offset: u16,
+}
+
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct TypeListEntry {
+    pub res_type: ResType,
+    /// One less than the number of resources of this type.
+    #[deku(endian = "big")]
+    pub count: u16,
+    /// Relative to the start of the type list.
+    #[deku(endian = "big")]
+    pub reference_list_offset: u16,
+}
+
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ReferenceListEntry {
+    #[deku(endian = "big")]
+    pub id: i16,
+    /// Relative to the start of the name list, or [`NO_NAME`].
+    #[deku(endian = "big")]
+    pub name_offset: u16,
+    pub attributes: ResourceAttributes,
+    /// Relative to the start of the resource data.
+    #[deku(endian = "big", bits = "24")]
+    pub data_offset: u32,
+    #[deku(endian = "big")]
+    pub handle: u32,
+}
+
+/// The attributes of the resource map as a whole.
+#[derive(DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct MapAttributes {
+    #[deku(bits = "1", pad_bits_before = "8")]
+    pub read_only: bool,
+    #[deku(bits = "1")]
+    pub compact: bool,
+    #[deku(bits = "1", pad_bits_after = "5")]
+    pub changed: bool,
+}
+
+impl fmt::Display for MapAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = vec![];
+        if self.read_only {
+            text.push("READ_ONLY");
+        }
+        if self.compact {
+            text.push("COMPACT");
+        }
+        if self.changed {
+            text.push("CHANGED");
+        }
+        write!(f, "{}", text.join("|"))
+    }
+}
+
+impl fmt::Debug for MapAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MapAttributes({})", self)
+    }
+}
+
+/// The attributes of a single resource, which tell the Resource Manager how
+/// to treat it once it has been loaded.
+#[derive(DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceAttributes {
+    #[deku(bits = "1", pad_bits_before = "1")]
+    pub system_heap: bool,
+    #[deku(bits = "1")]
+    pub purgeable: bool,
+    #[deku(bits = "1")]
+    pub locked: bool,
+    #[deku(bits = "1")]
+    pub protected: bool,
+    #[deku(bits = "1")]
+    pub preload: bool,
+    #[deku(bits = "1")]
+    pub changed: bool,
+    #[deku(bits = "1")]
+    pub compressed: bool,
+}
+
+impl fmt::Display for ResourceAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = vec![];
+        if self.system_heap {
+            text.push("SYSTEM_HEAP");
+        }
+        if self.purgeable {
+            text.push("PURGEABLE");
+        }
+        if self.locked {
+            text.push("LOCKED");
+        }
+        if self.protected {
+            text.push("PROTECTED");
+        }
+        if self.preload {
+            text.push("PRELOAD");
+        }
+        if self.changed {
+            text.push("CHANGED");
+        }
+        if self.compressed {
+            text.push("COMPRESSED");
+        }
+        write!(f, "{}", text.join("|"))
+    }
+}
+
+impl fmt::Debug for ResourceAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ResourceAttributes({})", self)
+    }
+}
+
+/// A resource listed in the map, without its data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resource {
+    res_type: ResType,
+    id: i16,
+    name: Option<Vec<u8>>,
+    attributes: ResourceAttributes,
+    offset: u64,
+    len: u64,
+}
+
+impl Resource {
+    pub fn res_type(&self) -> ResType {
+        self.res_type
+    }
+    pub fn id(&self) -> i16 {
+        self.id
+    }
+    pub fn name(&self) -> Option<&[u8]> {
+        self.name.as_deref()
+    }
+    pub fn attributes(&self) -> ResourceAttributes {
+        self.attributes
+    }
+    /// The position of the resource's data within the fork.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Finds `len` bytes at `start` within the map, or complains about the
+/// offset of the map plus `start`.
+fn map_slice(map: &[u8], map_offset: u64, start: usize, len: usize) -> error::Result<&[u8]> {
+    map.get(start..start + len)
+        .ok_or(Error::InvalidResourceMap { offset: map_offset + start as u64 })
+}
+
+/// The resource map of a resource fork, giving access to the resources in
+/// it.
+pub struct ResourceFile<R> {
+    fork: R,
+    attributes: MapAttributes,
+    resources: Vec<Resource>,
+}
+
+impl <R: Read + Seek> ResourceFile<R> {
+    /// Reads the resource map of `fork`. The data of the resources is only
+    /// read on request.
+    pub fn open(fork: ResourceFork<R>) -> error::Result<Self> {
+        let ResourceFork(mut fork) = fork;
+        let fork_len = fork.seek(SeekFrom::End(0))?;
+        fork.seek(SeekFrom::Start(0))?;
+        let mut bytes = [0u8; HEADER_LEN];
+        fork.read_exact(&mut bytes).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::TruncatedHeader { offset: 0 },
+            _ => e.into(),
+        })?;
+        let (_, header) = ResourceHeader::from_bytes((&bytes, 0))?;
+        let map_offset = header.map_offset as u64;
+        let data_offset = header.data_offset as u64;
+        if map_offset + header.map_len as u64 > fork_len
+            || (header.map_len as usize) < MAP_HEADER_LEN {
+            return Err(Error::InvalidResourceMap { offset: map_offset });
+        }
+        let mut map = vec![0u8; header.map_len as usize];
+        fork.seek(SeekFrom::Start(map_offset))?;
+        fork.read_exact(&mut map)?;
+
+        let (_, map_header) = MapHeader::from_bytes((&map, 0))?;
+        let type_list = map_header.type_list_offset as usize;
+        let name_list = map_header.name_list_offset as usize;
+        let count = map_slice(&map, map_offset, type_list, 2)?;
+        let n_types = u16::from_be_bytes([count[0], count[1]]).wrapping_add(1);
+
+        let mut resources = vec![];
+        for i in 0..n_types as usize {
+            let start = type_list + 2 + i * TYPE_LEN;
+            let bytes = map_slice(&map, map_offset, start, TYPE_LEN)?;
+            let (_, entry) = TypeListEntry::from_bytes((bytes, 0))?;
+            for j in 0..entry.count as usize + 1 {
+                let start = type_list + entry.reference_list_offset as usize + j * REFERENCE_LEN;
+                let bytes = map_slice(&map, map_offset, start, REFERENCE_LEN)?;
+                let (_, reference) = ReferenceListEntry::from_bytes((bytes, 0))?;
+                let name = match reference.name_offset {
+                    NO_NAME => None,
+                    name_offset => {
+                        let start = name_list + name_offset as usize;
+                        let len = map_slice(&map, map_offset, start, 1)?[0] as usize;
+                        Some(map_slice(&map, map_offset, start + 1, len)?.to_vec())
+                    },
+                };
+                let offset = data_offset + reference.data_offset as u64;
+                let mut len = [0u8; 4];
+                fork.seek(SeekFrom::Start(offset))?;
+                fork.read_exact(&mut len)
+                    .map_err(|_| Error::InvalidResourceMap { offset })?;
+                let len = u32::from_be_bytes(len) as u64;
+                if offset + 4 + len > fork_len {
+                    return Err(Error::InvalidResourceMap { offset });
+                }
+                resources.push(Resource {
+                    res_type: entry.res_type,
+                    id: reference.id,
+                    name,
+                    attributes: reference.attributes,
+                    offset: offset + 4,
+                    len,
+                });
+            }
+        }
+
+        Ok(Self {
+            fork,
+            attributes: map_header.attributes,
+            resources,
+        })
+    }
+    pub fn attributes(&self) -> MapAttributes {
+        self.attributes
+    }
+    /// Every resource, in the order they are listed in the map.
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+    /// The types of resource present, in the order they are listed in the
+    /// map.
+    pub fn types(&self) -> Vec<ResType> {
+        let mut types: Vec<ResType> = vec![];
+        for resource in &self.resources {
+            if !types.contains(&resource.res_type) {
+                types.push(resource.res_type);
+            }
+        }
+        types
+    }
+    /// Every resource of the given type.
+    pub fn resources_of_type(&self, res_type: ResType) -> impl Iterator<Item = &Resource> {
+        self.resources.iter()
+            .filter(move |resource| resource.res_type == res_type)
+    }
+    pub fn get(&self, res_type: ResType, id: i16) -> Option<&Resource> {
+        self.resources_of_type(res_type)
+            .find(|resource| resource.id == id)
+    }
+    pub fn get_named(&self, res_type: ResType, name: &[u8]) -> Option<&Resource> {
+        self.resources_of_type(res_type)
+            .find(|resource| resource.name() == Some(name))
+    }
+    /// Reads the data of a resource.
+    pub fn read(&mut self, resource: &Resource) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(resource.len as usize);
+        self.fork.seek(SeekFrom::Start(resource.offset))?;
+        (&mut self.fork).take(resource.len).read_to_end(&mut data)?;
+        Ok(data)
+    }
+    /// Reads the data of the resource with the given type and ID, if there
+    /// is one.
+    pub fn load(&mut self, res_type: ResType, id: i16) -> io::Result<Option<Vec<u8>>> {
+        match self.get(res_type, id).cloned() {
+            Some(resource) => self.read(&resource).map(Some),
+            None => Ok(None),
+        }
+    }
+    /// Reads and decodes the resource with the given ID and the type that
+    /// holds `T`, if there is one.
+    pub fn decode<T: ResourceData>(&mut self, id: i16) -> error::Result<Option<T>> {
+        match self.load(T::TYPE, id)? {
+            Some(data) => T::decode(&data).map(Some),
+            None => Ok(None),
+        }
+    }
+    pub fn into_inner(self) -> R {
+        self.fork
+    }
+}
+
+impl ResourceFile<Cursor<Vec<u8>>> {
+    /// Reads the resource fork of `archive` into memory and opens it, since
+    /// the forks an archive hands out cannot seek. Gives `None` if there is
+    /// no resource fork or it is empty.
+    pub fn from_archive<A: MultiForkArchive + ?Sized>(archive: &mut A) -> error::Result<Option<Self>> {
+        let rsrc = loose::read_rsrc_fork(archive)?;
+        if rsrc.is_empty() {
+            return Ok(None);
+        }
+        Self::open(ResourceFork(Cursor::new(rsrc))).map(Some)
+    }
+}
+
+/// The space left before the resource data, which the Resource Manager
+/// reserves for the system and the application.
+pub(crate) const DATA_OFFSET: usize = 256;
+
+/// A resource held in memory for writing.
+#[derive(Debug, Clone)]
+struct Item {
+    res_type: ResType,
+    id: i16,
+    name: Option<Vec<u8>>,
+    attributes: ResourceAttributes,
+    data: Vec<u8>,
+}
+
+/// Builds a resource fork from scratch or from an existing one, keeping the
+/// resources in memory until it is written.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceForkWriter {
+    attributes: MapAttributes,
+    items: Vec<Item>,
+}
+
+impl ResourceForkWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Loads every resource from `file` so that it can be edited.
+    pub fn edit<R: Read + Seek>(file: &mut ResourceFile<R>) -> io::Result<Self> {
+        let resources = file.resources().to_vec();
+        let mut items = Vec::with_capacity(resources.len());
+        for resource in resources {
+            let data = file.read(&resource)?;
+            let Resource { res_type, id, name, attributes, .. } = resource;
+            items.push(Item { res_type, id, name, attributes, data });
+        }
+        Ok(Self {
+            attributes: file.attributes(),
+            items,
+        })
+    }
+    fn find(&self, res_type: ResType, id: i16) -> Option<usize> {
+        self.items.iter()
+            .position(|item| item.res_type == res_type && item.id == id)
+    }
+    fn find_mut(&mut self, res_type: ResType, id: i16) -> Option<&mut Item> {
+        self.items.iter_mut()
+            .find(|item| item.res_type == res_type && item.id == id)
+    }
+    pub fn attributes(&mut self, attributes: MapAttributes) -> &Self {
+        self.attributes = attributes;
+        self
+    }
+    /// The type and ID of every resource, in the order they will be written.
+    pub fn resources(&self) -> impl Iterator<Item = (ResType, i16)> + '_ {
+        self.items.iter()
+            .map(|item| (item.res_type, item.id))
+    }
+    pub fn get(&self, res_type: ResType, id: i16) -> Option<&[u8]> {
+        self.find(res_type, id)
+            .map(|i| self.items[i].data.as_slice())
+    }
+    /// Adds a resource, or replaces the data of an existing one while keeping
+    /// its name and attributes.
+    pub fn insert(&mut self, res_type: ResType, id: i16, data: Vec<u8>) -> &Self {
+        match self.find_mut(res_type, id) {
+            Some(item) => item.data = data,
+            None => self.items.push(Item {
+                res_type,
+                id,
+                name: None,
+                attributes: ResourceAttributes::default(),
+                data,
+            }),
+        }
+        self
+    }
+    /// Encodes `value` as the resource with the given ID and the type that
+    /// holds `T`, as [`insert`](Self::insert) does.
+    pub fn encode<T: ResourceData>(&mut self, id: i16, value: &T) -> error::Result<&Self> {
+        let data = value.encode()?;
+        Ok(self.insert(T::TYPE, id, data))
+    }
+    /// Removes a resource, returning its data if it was present.
+    pub fn remove(&mut self, res_type: ResType, id: i16) -> Option<Vec<u8>> {
+        self.find(res_type, id)
+            .map(|i| self.items.remove(i).data)
+    }
+    /// Gives a resource a new ID. Fails if there is no such resource or the
+    /// new ID is already taken by another resource of the same type.
+    pub fn renumber(&mut self, res_type: ResType, id: i16, new_id: i16) -> bool {
+        if id != new_id && self.find(res_type, new_id).is_some() {
+            return false;
+        }
+        match self.find_mut(res_type, id) {
+            Some(item) => {
+                item.id = new_id;
+                true
+            },
+            None => false,
+        }
+    }
+    /// Names or unnames a resource. Fails if there is no such resource.
+    pub fn set_name(&mut self, res_type: ResType, id: i16, name: Option<Vec<u8>>) -> bool {
+        match self.find_mut(res_type, id) {
+            Some(item) => {
+                item.name = name;
+                true
+            },
+            None => false,
+        }
+    }
+    /// Changes the attributes of a resource. Fails if there is no such
+    /// resource.
+    pub fn set_attributes(
+        &mut self,
+        res_type: ResType,
+        id: i16,
+        attributes: ResourceAttributes,
+    ) -> bool {
+        match self.find_mut(res_type, id) {
+            Some(item) => {
+                item.attributes = attributes;
+                true
+            },
+            None => false,
+        }
+    }
+    /// Lays out the resource data and map as the Resource Manager would,
+    /// with the types in the order they first appear.
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<W> {
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidInput, message);
+
+        let mut types: Vec<ResType> = vec![];
+        for item in &self.items {
+            if !types.contains(&item.res_type) {
+                types.push(item.res_type);
+            }
+        }
+
+        let mut data = vec![];
+        let mut names = vec![];
+        let mut type_list = vec![];
+        let mut reference_lists = vec![];
+        let reference_list_start = 2 + types.len() * TYPE_LEN;
+        for res_type in &types {
+            let items: Vec<&Item> = self.items.iter()
+                .filter(|item| item.res_type == *res_type)
+                .collect();
+            let entry = TypeListEntry {
+                res_type: *res_type,
+                count: (items.len() - 1).try_into()
+                    .map_err(|_| invalid("resource map is too large"))?,
+                reference_list_offset: (reference_list_start + reference_lists.len())
+                    .try_into()
+                    .map_err(|_| invalid("resource map is too large"))?,
+            };
+            type_list.extend(entry.to_bytes()?);
+            for item in items {
+                let name_offset = match &item.name {
+                    Some(name) => {
+                        let offset = names.len();
+                        let len = u8::try_from(name.len())
+                            .map_err(|_| invalid("resource names must be at most 255 bytes"))?;
+                        names.push(len);
+                        names.extend_from_slice(name);
+                        offset.try_into()
+                            .ok()
+                            .filter(|offset| *offset != NO_NAME)
+                            .ok_or_else(|| invalid("resource name list is too large"))?
+                    },
+                    None => NO_NAME,
+                };
+                let data_offset = data.len() as u32;
+                if data_offset >= 1 << 24 {
+                    return Err(invalid("resource data is too large"));
+                }
+                let len = u32::try_from(item.data.len())
+                    .map_err(|_| invalid("resource data is too large"))?;
+                data.extend(len.to_be_bytes());
+                data.extend_from_slice(&item.data);
+                let reference = ReferenceListEntry {
+                    id: item.id,
+                    name_offset,
+                    attributes: item.attributes,
+                    data_offset,
+                    handle: 0,
+                };
+                reference_lists.extend(reference.to_bytes()?);
+            }
+        }
+
+        let type_count = u16::try_from(types.len())
+            .map_err(|_| invalid("resource map is too large"))?
+            .wrapping_sub(1);
+        let type_list_len = 2 + type_list.len() + reference_lists.len();
+        let name_list_offset = u16::try_from(MAP_HEADER_LEN + type_list_len)
+            .map_err(|_| invalid("resource map is too large"))?;
+        let map_len = MAP_HEADER_LEN + type_list_len + names.len();
+        let data_len = u32::try_from(data.len())
+            .map_err(|_| invalid("resource data is too large"))?;
+        let header = ResourceHeader {
+            data_offset: DATA_OFFSET as u32,
+            map_offset: DATA_OFFSET as u32 + data_len,
+            data_len,
+            map_len: map_len as u32,
+        };
+        let map_header = MapHeader {
+            header,
+            next_map: 0,
+            file_ref: 0,
+            attributes: self.attributes,
+            type_list_offset: MAP_HEADER_LEN as u16,
+            name_list_offset,
+        };
+
+        let header = header.to_bytes()?;
+        out.write_all(&header)?;
+        out.write_all(&[0u8; DATA_OFFSET - HEADER_LEN])?;
+        out.write_all(&data)?;
+        out.write_all(&map_header.to_bytes()?)?;
+        out.write_all(&type_count.to_be_bytes())?;
+        out.write_all(&type_list)?;
+        out.write_all(&reference_lists)?;
+        out.write_all(&names)?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Entry, SeekableArchive, format::Format};
+
+    fn reopen(writer: &ResourceForkWriter) -> (Vec<u8>, ResourceFile<Cursor<Vec<u8>>>) {
+        let bytes = writer.write(vec![]).unwrap();
+        let file = ResourceFile::open(ResourceFork(Cursor::new(bytes.clone()))).unwrap();
+        (bytes, file)
+    }
+
+    #[test]
+    fn writer_round_trips_through_reader() {
+        let (text, icon, vers) = (ResType::new(*b"TEXT"), ResType::new(*b"ICN#"), ResType::new(*b"vers"));
+        let mut writer = ResourceForkWriter::new();
+        writer.attributes(MapAttributes { read_only: true, ..Default::default() });
+        writer.insert(text, 128, b"first".to_vec());
+        writer.insert(icon, -4000, vec![0xff; 256]);
+        writer.insert(text, 129, vec![]);
+        writer.insert(vers, 1, b"\x01\x00\x80\x00".to_vec());
+        writer.set_name(text, 128, Some(b"Read Me".to_vec()));
+        writer.set_name(vers, 1, Some(b"\xa9 Version".to_vec()));
+        let attributes = ResourceAttributes { purgeable: true, preload: true, ..Default::default() };
+        writer.set_attributes(icon, -4000, attributes);
+
+        let (bytes, mut file) = reopen(&writer);
+        assert!(file.attributes().read_only);
+        assert_eq!(file.types(), [text, icon, vers]);
+        assert_eq!(file.resources().len(), 4);
+        for (res_type, id) in writer.resources() {
+            let resource = file.get(res_type, id).unwrap().clone();
+            assert_eq!(file.read(&resource).unwrap(), writer.get(res_type, id).unwrap());
+        }
+        assert_eq!(file.get(text, 128).unwrap().name(), Some(&b"Read Me"[..]));
+        assert_eq!(file.get(text, 129).unwrap().name(), None);
+        assert_eq!(file.get_named(vers, b"\xa9 Version").unwrap().id(), 1);
+        assert_eq!(file.get(icon, -4000).unwrap().attributes(), attributes);
+        assert_eq!(file.get(text, 128).unwrap().attributes(), ResourceAttributes::default());
+
+        let edited = ResourceForkWriter::edit(&mut file).unwrap();
+        assert_eq!(edited.write(vec![]).unwrap(), bytes);
+    }
+
+    #[test]
+    fn writes_empty_map() {
+        let (bytes, file) = reopen(&ResourceForkWriter::new());
+        assert!(file.resources().is_empty());
+        assert!(file.types().is_empty());
+        // the type count is stored as one less than the number of types.
+        let map_offset = u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(bytes[map_offset + MAP_HEADER_LEN..][..2], [0xff, 0xff]);
+    }
+
+    #[test]
+    fn rejects_map_past_name_list_offset() {
+        // The name list follows the map header, the type list and 12 bytes
+        // for each resource, and its offset must fit in 16 bits.
+        let fits = (u16::MAX as usize - MAP_HEADER_LEN - 2 - TYPE_LEN) / 12;
+        let mut writer = ResourceForkWriter::new();
+        for id in 0..fits as i16 {
+            writer.insert(ResType::new(*b"DATA"), id, vec![]);
+        }
+        let (_, file) = reopen(&writer);
+        assert_eq!(file.resources().len(), fits);
+
+        writer.insert(ResType::new(*b"DATA"), fits as i16, vec![]);
+        let error = writer.write(vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(error.to_string(), "resource map is too large");
+    }
+
+    #[test]
+    fn opens_resource_fork_of_archive() {
+        let vers = ResType::new(*b"vers");
+        let mut writer = ResourceForkWriter::new();
+        writer.insert(vers, 1, b"\x01\x00\x80\x00".to_vec());
+        let rsrc = writer.write(vec![]).unwrap();
+        let len = rsrc.len() as u64;
+        let mut file = b"data".to_vec();
+        file.extend(rsrc);
+
+        let mut builder = SeekableArchive::builder(Cursor::new(file));
+        builder.archive().format(Format::AppleSingle);
+        builder.rsrc_fork(Entry { id: 2, offset: 4, len });
+        let mut archive = builder.build().unwrap();
+        let mut fork = ResourceFile::from_archive(&mut archive).unwrap().unwrap();
+        assert_eq!(fork.load(vers, 1).unwrap().unwrap(), b"\x01\x00\x80\x00");
+
+        let mut archive = archive.into_archive();
+        assert!(ResourceFile::from_archive(&mut archive).unwrap().is_none());
+    }
+}
@@ -0,0 +1,347 @@
+//! Decoders for the resources which tell the Finder what an application
+//! owns: its bundle, file references and signature.
+
+use std::io::{
+    Seek,
+    prelude::*,
+};
+
+use deku::prelude::*;
+
+use super::{
+    IconFamily,
+    PascalString,
+    ResType,
+    ResourceData,
+    ResourceFile,
+};
+use crate::{
+    Creator,
+    FileType,
+    FinderInfo,
+    FourCC,
+    error::{self, Error},
+};
+
+/// The resource type of a file reference.
+pub const FREF: ResType = ResType::new(*b"FREF");
+/// The resource type of the icon lists in a bundle.
+const ICON_LIST: ResType = ResType::new(*b"ICN#");
+
+/// Maps an ID used within a bundle to the ID of an actual resource.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+pub struct LocalId {
+    #[deku(endian = "big")]
+    pub local_id: i16,
+    #[deku(endian = "big")]
+    pub resource_id: i16,
+}
+
+/// The resources of one type that belong to a bundle.
+#[derive(Debug, DekuRead, DekuWrite, Clone, PartialEq, Eq)]
+pub struct BundleResources {
+    pub res_type: ResType,
+    /// One less than the number of IDs.
+    #[deku(endian = "big")]
+    count: u16,
+    #[deku(count = "*count as usize + 1")]
+    ids: Vec<LocalId>,
+}
+
+impl BundleResources {
+    pub fn ids(&self) -> &[LocalId] {
+        &self.ids
+    }
+}
+
+/// The contents of a `BNDL` resource, which ties an application's signature
+/// to the file references and icons it provides.
+#[derive(Debug, DekuRead, DekuWrite, Clone, PartialEq, Eq)]
+pub struct Bundle {
+    pub signature: Creator,
+    /// The ID of the signature resource, whose type is the signature.
+    #[deku(endian = "big")]
+    pub signature_id: i16,
+    /// One less than the number of types.
+    #[deku(endian = "big")]
+    count: u16,
+    #[deku(count = "*count as usize + 1")]
+    types: Vec<BundleResources>,
+}
+
+impl Bundle {
+    pub fn types(&self) -> &[BundleResources] {
+        &self.types
+    }
+    /// Finds the ID of the resource which the bundle knows by `local_id`.
+    pub fn resolve(&self, res_type: ResType, local_id: i16) -> Option<i16> {
+        self.types.iter()
+            .filter(|resources| resources.res_type == res_type)
+            .flat_map(|resources| resources.ids.iter())
+            .find(|id| id.local_id == local_id)
+            .map(|id| id.resource_id)
+    }
+    /// Follows the bundle's file references to the file types it claims and
+    /// the icons it gives them.
+    pub fn summarize<R: Read + Seek>(
+        &self,
+        file: &mut ResourceFile<R>,
+    ) -> error::Result<BundleSummary> {
+        let signature_type = ResType::from(FourCC::from(self.signature));
+        let signature = file.load(signature_type, self.signature_id)?;
+        let mut file_types = vec![];
+        let references: Vec<i16> = self.types.iter()
+            .filter(|resources| resources.res_type == FREF)
+            .flat_map(|resources| resources.ids.iter())
+            .map(|id| id.resource_id)
+            .collect();
+        for id in references {
+            let reference: FileReference = match file.decode(id)? {
+                Some(reference) => reference,
+                None => continue,
+            };
+            file_types.push(ClaimedType {
+                file_type: reference.file_type,
+                icon_id: self.resolve(ICON_LIST, reference.icon_id),
+                name: reference.name,
+            });
+        }
+        Ok(BundleSummary {
+            signature: self.signature,
+            signature_data: signature,
+            file_types,
+        })
+    }
+}
+
+impl ResourceData for Bundle {
+    const TYPE: ResType = ResType::new(*b"BNDL");
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        Ok(Self::from_bytes((data, 0))?.1)
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        Ok(self.to_bytes()?)
+    }
+}
+
+/// The contents of a `FREF` resource, naming a file type that an
+/// application owns and the icon it is shown with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileReference {
+    pub file_type: FileType,
+    /// The ID of the icon within the bundle, rather than that of a resource.
+    pub icon_id: i16,
+    /// A file that must travel with the application, which older versions of
+    /// the resource leave out.
+    pub name: Option<PascalString>,
+}
+
+impl ResourceData for FileReference {
+    const TYPE: ResType = FREF;
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        if data.len() < 6 {
+            return Err(Error::InvalidResource { res_type: FREF });
+        }
+        let file_type = FileType::from(<[u8; 4]>::try_from(&data[..4]).unwrap());
+        let icon_id = i16::from_be_bytes([data[4], data[5]]);
+        let name = match data.len() {
+            6 => None,
+            _ => Some(PascalString::decode(&data[6..])?),
+        };
+        Ok(Self { file_type, icon_id, name })
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        let mut data = FourCC::from(self.file_type).bytes().to_vec();
+        data.extend(self.icon_id.to_be_bytes());
+        if let Some(name) = &self.name {
+            data.extend(name.encode()?);
+        }
+        Ok(data)
+    }
+}
+
+/// A file type which an application claims.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimedType {
+    pub file_type: FileType,
+    /// The ID of the icon family for files of this type, if the bundle has
+    /// one.
+    pub icon_id: Option<i16>,
+    pub name: Option<PascalString>,
+}
+
+impl ClaimedType {
+    /// Gathers the icon family for files of this type.
+    pub fn icon<R: Read + Seek>(
+        &self,
+        file: &mut ResourceFile<R>,
+    ) -> error::Result<Option<IconFamily>> {
+        match self.icon_id {
+            Some(id) => IconFamily::load(file, id).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// What an application's bundle says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BundleSummary {
+    pub signature: Creator,
+    /// The contents of the signature resource, which is usually a Pascal
+    /// string describing the application.
+    pub signature_data: Option<Vec<u8>>,
+    pub file_types: Vec<ClaimedType>,
+}
+
+/// Finds and summarizes the bundle of a file whose Finder info says it has
+/// one, preferring the bundle whose signature is the file's creator. Bundles
+/// which cannot be decoded are passed over, and only fail the search if no
+/// other bundle can be used.
+pub fn find_bundle<R: Read + Seek>(
+    finder_info: &FinderInfo,
+    file: &mut ResourceFile<R>,
+) -> error::Result<Option<BundleSummary>> {
+    if !finder_info.flags.has_bundle {
+        return Ok(None);
+    }
+    let ids: Vec<i16> = file.resources_of_type(Bundle::TYPE)
+        .map(|resource| resource.id())
+        .collect();
+    let mut bundles = vec![];
+    let mut error = None;
+    for id in ids {
+        let data = file.load(Bundle::TYPE, id)?.unwrap_or_default();
+        match Bundle::decode(&data) {
+            Ok(bundle) => bundles.push(bundle),
+            Err(e) => {
+                error.get_or_insert(e);
+            },
+        }
+    }
+    let bundle = bundles.iter()
+        .find(|bundle| bundle.signature == finder_info.creator)
+        .or(bundles.first());
+    match (bundle, error) {
+        (Some(bundle), _) => bundle.summarize(file).map(Some),
+        (None, Some(e)) => Err(e),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::{
+        ResourceFork,
+        rsrc::ResourceForkWriter,
+    };
+
+    const BNDL: ResType = Bundle::TYPE;
+
+    /// A bundle with one icon list and one file reference, both numbered 0
+    /// within the bundle.
+    fn bundle(signature: &[u8; 4], fref: i16) -> Vec<u8> {
+        [
+            signature.as_slice(),
+            b"\x00\x00\x00\x01",
+            b"ICN#\x00\x00\x00\x00\x00\x80",
+            b"FREF\x00\x00\x00\x00",
+            &fref.to_be_bytes(),
+        ].concat()
+    }
+
+    fn finder_info(creator: &[u8; 4]) -> FinderInfo {
+        let (_, mut finder_info) = FinderInfo::from_bytes((&[0; 16], 0)).unwrap();
+        finder_info.creator = Creator::from(*creator);
+        finder_info.flags.has_bundle = true;
+        finder_info
+    }
+
+    fn open(writer: &ResourceForkWriter) -> ResourceFile<Cursor<Vec<u8>>> {
+        let bytes = writer.write(vec![]).unwrap();
+        ResourceFile::open(ResourceFork(Cursor::new(bytes))).unwrap()
+    }
+
+    #[test]
+    fn decodes_bundle() {
+        let data = bundle(b"TEST", 128);
+        let bundle = Bundle::decode(&data).unwrap();
+        assert_eq!(bundle.signature, Creator::from(*b"TEST"));
+        assert_eq!(bundle.signature_id, 0);
+        let types: Vec<ResType> = bundle.types().iter().map(|resources| resources.res_type).collect();
+        assert_eq!(types, [ICON_LIST, FREF]);
+        assert_eq!(bundle.types()[1].ids(), [LocalId { local_id: 0, resource_id: 128 }]);
+        assert_eq!(bundle.resolve(FREF, 0), Some(128));
+        assert_eq!(bundle.resolve(FREF, 1), None);
+        assert_eq!(bundle.resolve(BNDL, 0), None);
+        assert_eq!(bundle.encode().unwrap(), data);
+        assert!(Bundle::decode(&data[..data.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn decodes_file_reference() {
+        let reference = FileReference::decode(b"TEXT\x00\x01").unwrap();
+        assert_eq!(reference.file_type, FileType::from(*b"TEXT"));
+        assert_eq!(reference.icon_id, 1);
+        assert_eq!(reference.name, None);
+        assert_eq!(reference.encode().unwrap(), b"TEXT\x00\x01");
+
+        let reference = FileReference::decode(b"PREF\xff\xff\x05Prefs").unwrap();
+        assert_eq!(reference.icon_id, -1);
+        assert_eq!(reference.name.as_ref().unwrap().bytes(), b"Prefs");
+        assert_eq!(reference.encode().unwrap(), b"PREF\xff\xff\x05Prefs");
+
+        assert!(matches!(
+            FileReference::decode(b"TEXT\x00"),
+            Err(Error::InvalidResource { res_type: FREF }),
+        ));
+    }
+
+    #[test]
+    fn prefers_bundle_of_creator() {
+        let mut writer = ResourceForkWriter::new();
+        writer.insert(BNDL, 128, bundle(b"OTHR", 128));
+        writer.insert(BNDL, 129, bundle(b"TEST", 129));
+        writer.insert(FREF, 128, b"OTHR\x00\x00".to_vec());
+        writer.insert(FREF, 129, b"TEXT\x00\x00\x05Prefs".to_vec());
+        writer.insert(ResType::new(*b"TEST"), 0, b"\x08Test app".to_vec());
+        let mut file = open(&writer);
+
+        let summary = find_bundle(&finder_info(b"TEST"), &mut file).unwrap().unwrap();
+        assert_eq!(summary.signature, Creator::from(*b"TEST"));
+        assert_eq!(summary.signature_data.as_deref(), Some(&b"\x08Test app"[..]));
+        assert_eq!(summary.file_types, [ClaimedType {
+            file_type: FileType::from(*b"TEXT"),
+            icon_id: Some(128),
+            name: PascalString::new(b"Prefs".to_vec()),
+        }]);
+
+        // without a bundle of its own, the file gets the first one.
+        let summary = find_bundle(&finder_info(b"NONE"), &mut file).unwrap().unwrap();
+        assert_eq!(summary.signature, Creator::from(*b"OTHR"));
+        assert_eq!(summary.signature_data, None);
+        assert_eq!(summary.file_types[0].file_type, FileType::from(*b"OTHR"));
+
+        let mut finder_info = finder_info(b"TEST");
+        finder_info.flags.has_bundle = false;
+        assert!(find_bundle(&finder_info, &mut file).unwrap().is_none());
+    }
+
+    #[test]
+    fn skips_malformed_bundle() {
+        let mut writer = ResourceForkWriter::new();
+        writer.insert(BNDL, 128, b"TEST\x00".to_vec());
+        let mut file = open(&writer);
+        assert!(find_bundle(&finder_info(b"TEST"), &mut file).is_err());
+
+        writer.insert(BNDL, 129, bundle(b"OTHR", 128));
+        let mut file = open(&writer);
+        let summary = find_bundle(&finder_info(b"TEST"), &mut file).unwrap().unwrap();
+        assert_eq!(summary.signature, Creator::from(*b"OTHR"));
+        assert!(summary.file_types.is_empty());
+
+        assert!(find_bundle(&finder_info(b"TEST"), &mut open(&ResourceForkWriter::new())).unwrap().is_none());
+    }
+}
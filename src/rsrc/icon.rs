@@ -0,0 +1,807 @@
+//! Rendering of icons stored in resources and in archives.
+
+use std::io::{
+    self,
+    Seek,
+    prelude::*,
+};
+
+use deku::prelude::*;
+
+use super::{
+    ResType,
+    ResourceFile,
+};
+use crate::{
+    Rect,
+    SeekableArchive,
+    applesingle::EntryType,
+    error::{self, Error},
+};
+
+/// The colors of the standard 4-bit palette.
+const PALETTE_4: [[u8; 3]; 16] = [
+    [0xff, 0xff, 0xff],
+    [0xfc, 0xf3, 0x05],
+    [0xff, 0x64, 0x02],
+    [0xdd, 0x08, 0x06],
+    [0xf2, 0x08, 0x84],
+    [0x46, 0x00, 0xa5],
+    [0x00, 0x00, 0xd4],
+    [0x02, 0xab, 0xea],
+    [0x1f, 0xb7, 0x14],
+    [0x00, 0x64, 0x11],
+    [0x56, 0x2c, 0x05],
+    [0x90, 0x71, 0x3a],
+    [0xc0, 0xc0, 0xc0],
+    [0x80, 0x80, 0x80],
+    [0x40, 0x40, 0x40],
+    [0x00, 0x00, 0x00],
+];
+
+/// The standard 8-bit palette: a cube of 215 colors, then ramps of red,
+/// green, blue and gray which skip the levels already in the cube, then
+/// black.
+fn palette_8() -> [[u8; 3]; 256] {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut palette = [[0u8; 3]; 256];
+    for (i, color) in palette.iter_mut().take(215).enumerate() {
+        *color = [CUBE[i / 36], CUBE[i / 6 % 6], CUBE[i % 6]];
+    }
+    for (i, level) in RAMP.iter().enumerate() {
+        palette[215 + i] = [*level, 0, 0];
+        palette[225 + i] = [0, *level, 0];
+        palette[235 + i] = [0, 0, *level];
+        palette[245 + i] = [*level, *level, *level];
+    }
+    palette
+}
+
+/// An image with 8 bits each of red, green, blue and alpha per pixel.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    /// The pixels, row by row from the top.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    pub fn write_png<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// Unpacks the palette indices of a bitmap with `depth` bits per pixel. Each
+/// row must hold at least `width` pixels.
+fn unpack(data: &[u8], depth: usize, row_bytes: usize, width: usize, height: usize) -> Vec<u8> {
+    if row_bytes == 0 {
+        return vec![];
+    }
+    let mask = ((1u16 << depth) - 1) as u8;
+    let mut indices = Vec::with_capacity(width * height);
+    for row in data.chunks(row_bytes).take(height) {
+        for x in 0..width {
+            let bit = x * depth;
+            let shift = 8 - depth - bit % 8;
+            indices.push(row[bit / 8] >> shift & mask);
+        }
+    }
+    indices
+}
+
+/// Combines palette indices and optional 1-bit mask into an image.
+fn paint(
+    indices: &[u8],
+    palette: &[[u8; 3]],
+    mask: Option<&[u8]>,
+    width: usize,
+    height: usize,
+) -> Image {
+    let mut pixels = Vec::with_capacity(width * height * 4);
+    for (i, index) in indices.iter().enumerate() {
+        let [r, g, b] = palette.get(*index as usize).copied().unwrap_or_default();
+        let alpha = match mask {
+            Some(mask) if mask[i] == 0 => 0,
+            _ => 0xff,
+        };
+        pixels.extend([r, g, b, alpha]);
+    }
+    Image {
+        width: width as u32,
+        height: height as u32,
+        pixels,
+    }
+}
+
+/// Expands the run-length encoded channels of a 32-bit icon.
+fn unpack_rle(data: &[u8], pixels: usize) -> Option<Vec<u8>> {
+    let mut channels = Vec::with_capacity(pixels * 3);
+    let mut data = data.iter().copied();
+    while channels.len() < pixels * 3 {
+        let n = data.next()?;
+        if n < 0x80 {
+            for _ in 0..=n {
+                channels.push(data.next()?);
+            }
+        } else {
+            let b = data.next()?;
+            channels.extend(std::iter::repeat_n(b, n as usize - 125));
+        }
+    }
+    channels.truncate(pixels * 3);
+    Some(channels)
+}
+
+/// A member of an icon family, as found in a resource fork or an `icns`
+/// resource.
+struct Member {
+    res_type: ResType,
+    width: usize,
+    height: usize,
+    depth: usize,
+    /// Where the mask comes from, which is the member itself for 1-bit icons.
+    mask: Option<ResType>,
+}
+
+const fn member(
+    code: &[u8; 4],
+    size: (usize, usize),
+    depth: usize,
+    mask: Option<&[u8; 4]>,
+) -> Member {
+    let mask = match mask {
+        Some(mask) => Some(ResType::new(*mask)),
+        None => None,
+    };
+    Member {
+        res_type: ResType::new(*code),
+        width: size.0,
+        height: size.1,
+        depth,
+        mask,
+    }
+}
+
+/// Every member which can be rendered, from the most to the least detailed.
+const MEMBERS: [Member; 16] = [
+    member(b"it32", (128, 128), 32, Some(b"t8mk")),
+    member(b"ih32", (48, 48), 32, Some(b"h8mk")),
+    member(b"ich8", (48, 48), 8, Some(b"ich#")),
+    member(b"ich4", (48, 48), 4, Some(b"ich#")),
+    member(b"ich#", (48, 48), 1, Some(b"ich#")),
+    member(b"il32", (32, 32), 32, Some(b"l8mk")),
+    member(b"icl8", (32, 32), 8, Some(b"ICN#")),
+    member(b"icl4", (32, 32), 4, Some(b"ICN#")),
+    member(b"ICN#", (32, 32), 1, Some(b"ICN#")),
+    member(b"is32", (16, 16), 32, Some(b"s8mk")),
+    member(b"ics8", (16, 16), 8, Some(b"ics#")),
+    member(b"ics4", (16, 16), 4, Some(b"ics#")),
+    member(b"ics#", (16, 16), 1, Some(b"ics#")),
+    member(b"icm8", (16, 12), 8, Some(b"icm#")),
+    member(b"icm4", (16, 12), 4, Some(b"icm#")),
+    member(b"icm#", (16, 12), 1, Some(b"icm#")),
+];
+
+/// The resource type of an icon family in a single resource.
+pub const ICNS: ResType = ResType::new(*b"icns");
+
+/// The versions of an icon at different sizes and depths, along with their
+/// masks.
+#[derive(Debug, Clone, Default)]
+pub struct IconFamily {
+    members: Vec<(ResType, Vec<u8>)>,
+}
+
+impl IconFamily {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Gathers the members of the family with the given ID from a resource
+    /// fork, including those in an `icns` resource.
+    pub fn load<R: Read + Seek>(file: &mut ResourceFile<R>, id: i16) -> error::Result<Self> {
+        let mut family = match file.load(ICNS, id)? {
+            Some(data) => Self::parse_icns(&data)?,
+            None => Self::new(),
+        };
+        for member in &MEMBERS {
+            let data = match member.depth {
+                32 => None,
+                _ => file.load(member.res_type, id)?,
+            };
+            if let Some(data) = data {
+                family.insert(member.res_type, data);
+            }
+        }
+        Ok(family)
+    }
+    /// Reads the members of an `icns` resource or file.
+    pub fn parse_icns(data: &[u8]) -> error::Result<Self> {
+        let invalid = Error::InvalidResource { res_type: ICNS };
+        if data.get(..4) != Some(b"icns") {
+            return Err(invalid);
+        }
+        // The members end where the header says the family does.
+        let len = data.get(4..8)
+            .map(|len| u32::from_be_bytes(len.try_into().unwrap()) as usize)
+            .filter(|len| *len >= 8);
+        let Some(data) = len.and_then(|len| data.get(..len)) else {
+            return Err(invalid);
+        };
+        let mut family = Self::new();
+        let mut offset = 8;
+        while offset + 8 <= data.len() {
+            let res_type = ResType::new(data[offset..offset + 4].try_into().unwrap());
+            let len = u32::from_be_bytes(data[offset + 4..offset + 8].try_into().unwrap());
+            let end = offset + len as usize;
+            if len < 8 || end > data.len() {
+                return Err(invalid);
+            }
+            family.insert(res_type, data[offset + 8..end].to_vec());
+            offset = end;
+        }
+        Ok(family)
+    }
+    /// Adds or replaces a member.
+    pub fn insert(&mut self, res_type: ResType, data: Vec<u8>) {
+        self.members.retain(|(member, _)| *member != res_type);
+        self.members.push((res_type, data));
+    }
+    pub fn get(&self, res_type: ResType) -> Option<&[u8]> {
+        self.members.iter()
+            .find(|(member, _)| *member == res_type)
+            .map(|(_, data)| data.as_slice())
+    }
+    pub fn types(&self) -> impl Iterator<Item = ResType> + '_ {
+        self.members.iter()
+            .map(|(res_type, _)| *res_type)
+    }
+    /// Renders one member, masked by the member that holds its mask. Members
+    /// which are absent or cannot be rendered, such as the PNG and JPEG 2000
+    /// members of newer `icns` files, give `None`.
+    pub fn render(&self, res_type: ResType) -> error::Result<Option<Image>> {
+        let member = match MEMBERS.iter().find(|member| member.res_type == res_type) {
+            Some(member) => member,
+            None => return Ok(None),
+        };
+        let data = match self.get(res_type) {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let Member { width, height, depth, .. } = *member;
+        let pixels = width * height;
+        let invalid = || Error::InvalidResource { res_type };
+        if depth == 32 {
+            // The largest size has four bytes of zeroes before its channels.
+            let data = match res_type == ResType::new(*b"it32") {
+                true => data.get(4..).ok_or_else(invalid)?,
+                false => data,
+            };
+            let channels = if data.len() == pixels * 4 {
+                data.chunks(4).flat_map(|argb| argb[1..].to_vec()).collect()
+            } else {
+                let planar = unpack_rle(data, pixels)
+                    .ok_or_else(invalid)?;
+                (0..pixels)
+                    .flat_map(|i| [planar[i], planar[pixels + i], planar[2 * pixels + i]])
+                    .collect::<Vec<u8>>()
+            };
+            let alpha = member.mask
+                .and_then(|mask| self.get(mask))
+                .filter(|mask| mask.len() >= pixels);
+            let pixels = channels.chunks(3)
+                .enumerate()
+                .flat_map(|(i, rgb)| [rgb[0], rgb[1], rgb[2], alpha.map_or(0xff, |a| a[i])])
+                .collect();
+            return Ok(Some(Image {
+                width: width as u32,
+                height: height as u32,
+                pixels,
+            }));
+        }
+        let len = pixels * depth / 8;
+        if data.len() < len {
+            return Err(invalid());
+        }
+        let mask_len = pixels / 8;
+        let mask = member.mask
+            .and_then(|mask| self.get(mask))
+            .and_then(|mask| mask.get(mask_len..mask_len * 2))
+            .map(|mask| unpack(mask, 1, width / 8, width, height));
+        let indices = unpack(data, depth, width * depth / 8, width, height);
+        let image = match depth {
+            1 => paint(&indices, &[[0xff; 3], [0; 3]], mask.as_deref(), width, height),
+            4 => paint(&indices, &PALETTE_4, mask.as_deref(), width, height),
+            _ => paint(&indices, &palette_8(), mask.as_deref(), width, height),
+        };
+        Ok(Some(image))
+    }
+    /// Renders the most detailed member which can be rendered.
+    pub fn best(&self) -> error::Result<Option<Image>> {
+        for member in &MEMBERS {
+            if let Some(image) = self.render(member.res_type)? {
+                return Ok(Some(image));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, DekuRead)]
+struct PixMap {
+    #[deku(endian = "big")]
+    _base_addr: u32,
+    #[deku(endian = "big")]
+    row_bytes: u16,
+    bounds: Rect,
+    #[deku(endian = "big")]
+    _version: i16,
+    #[deku(endian = "big")]
+    _pack_type: i16,
+    #[deku(endian = "big")]
+    _pack_size: u32,
+    #[deku(endian = "big")]
+    _h_res: u32,
+    #[deku(endian = "big")]
+    _v_res: u32,
+    #[deku(endian = "big")]
+    _pixel_type: i16,
+    #[deku(endian = "big")]
+    pixel_size: u16,
+    #[deku(endian = "big")]
+    _cmp_count: u16,
+    #[deku(endian = "big")]
+    _cmp_size: u16,
+    #[deku(endian = "big")]
+    _plane_bytes: u32,
+    #[deku(endian = "big")]
+    _table: u32,
+    #[deku(endian = "big")]
+    _reserved: u32,
+}
+
+#[derive(Debug, DekuRead)]
+struct BitMap {
+    #[deku(endian = "big")]
+    _base_addr: u32,
+    #[deku(endian = "big")]
+    row_bytes: u16,
+    bounds: Rect,
+}
+
+/// The fixed part of a `cicn` resource, which is followed by the mask, the
+/// 1-bit icon, the color table and the pixels.
+#[derive(Debug, DekuRead)]
+struct ColorIconHeader {
+    pixmap: PixMap,
+    mask: BitMap,
+    bitmap: BitMap,
+    #[deku(endian = "big")]
+    _icon_data: u32,
+}
+
+#[derive(Debug, DekuRead)]
+struct ColorSpec {
+    #[deku(endian = "big")]
+    value: u16,
+    #[deku(endian = "big")]
+    red: u16,
+    #[deku(endian = "big")]
+    green: u16,
+    #[deku(endian = "big")]
+    blue: u16,
+}
+
+#[deku_derive(DekuRead)]
+#[derive(Debug)]
+struct ColorTable {
+    #[deku(endian = "big")]
+    _seed: u32,
+    #[deku(endian = "big")]
+    flags: u16,
+    /// One less than the number of entries, the index of the last one. Only
+    /// needed to read the entries, so it is not kept.
+    #[deku(endian = "big", temp)]
+    last_index: u16,
+    #[deku(count = "*last_index as usize + 1")]
+    entries: Vec<ColorSpec>,
+}
+
+impl ColorTable {
+    /// The colors by pixel value, which for device tables is the position of
+    /// the color in the table.
+    fn palette(&self) -> Vec<[u8; 3]> {
+        let mut palette = vec![[0u8; 3]; 256];
+        for (i, spec) in self.entries.iter().enumerate() {
+            let index = match self.flags & 0x8000 {
+                0 => spec.value as usize,
+                _ => i,
+            };
+            if let Some(color) = palette.get_mut(index) {
+                *color = [spec.red, spec.green, spec.blue].map(|c| (c >> 8) as u8);
+            }
+        }
+        palette
+    }
+}
+
+fn bounds_size(bounds: &Rect) -> (usize, usize) {
+    let width = (bounds.right as isize - bounds.left as isize).max(0);
+    let height = (bounds.bottom as isize - bounds.top as isize).max(0);
+    (width as usize, height as usize)
+}
+
+/// The resource type of a color icon.
+pub const CICN: ResType = ResType::new(*b"cicn");
+
+/// Renders the contents of a `cicn` resource, which is also the format of
+/// the color icon entry of an AppleSingle or AppleDouble file.
+pub fn render_color_icon(data: &[u8]) -> error::Result<Image> {
+    let invalid = || Error::InvalidResource { res_type: CICN };
+    let (rest, header) = ColorIconHeader::from_bytes((data, 0))?;
+    let mut offset = data.len() - rest.0.len();
+    let ColorIconHeader { pixmap, mask, bitmap, .. } = header;
+
+    let (width, height) = bounds_size(&pixmap.bounds);
+    let (mask_width, mask_height) = bounds_size(&mask.bounds);
+    let mask_len = mask.row_bytes as usize * mask_height;
+    let mask_data = data.get(offset..offset + mask_len).ok_or_else(invalid)?;
+    offset += mask_len + bitmap.row_bytes as usize * bounds_size(&bitmap.bounds).1;
+
+    let (rest, table) = ColorTable::from_bytes((data.get(offset..).ok_or_else(invalid)?, 0))?;
+    offset = data.len() - rest.0.len();
+
+    let depth = pixmap.pixel_size as usize;
+    if !matches!(depth, 1 | 2 | 4 | 8) {
+        return Err(invalid());
+    }
+    let row_bytes = (pixmap.row_bytes & 0x3fff) as usize;
+    if row_bytes * 8 < width * depth {
+        return Err(invalid());
+    }
+    let mask_row_bytes = mask.row_bytes as usize;
+    if mask_row_bytes > 0 && mask_row_bytes * 8 < mask_width {
+        return Err(invalid());
+    }
+    let pixels = data.get(offset..offset + row_bytes * height).ok_or_else(invalid)?;
+    let indices = unpack(pixels, depth, row_bytes, width, height);
+    let mask = match (mask_width, mask_height) == (width, height) && mask_row_bytes > 0 {
+        true => Some(unpack(mask_data, 1, mask_row_bytes, width, height)),
+        false => None,
+    };
+    Ok(paint(&indices, &table.palette(), mask.as_deref(), width, height))
+}
+
+/// Renders the icon stored in an archive's own entries, preferring the color
+/// icon over the black and white one.
+pub fn archive_icon<R: Read + Seek>(
+    archive: &mut SeekableArchive<R>,
+) -> error::Result<Option<Image>> {
+    if let Some(mut entry) = archive.entry_by_id(EntryType::IconColor.into())? {
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+        return render_color_icon(&data).map(Some);
+    }
+    if let Some(mut entry) = archive.entry_by_id(EntryType::IconBW.into())? {
+        let mut data = vec![];
+        entry.read_to_end(&mut data)?;
+        let mut family = IconFamily::new();
+        family.insert(ResType::new(*b"ICN#"), data);
+        return family.render(ResType::new(*b"ICN#"));
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ResourceFork,
+        applesingle::{self, APPLESINGLE_MAGIC},
+        rsrc::ResourceForkWriter,
+        testing,
+    };
+
+    const ICN: ResType = ResType::new(*b"ICN#");
+    const IS32: ResType = ResType::new(*b"is32");
+
+    /// A 1-bit icon of `pixels` pixels followed by its mask, each starting
+    /// with the given bytes and clear after them.
+    fn one_bit(pixels: usize, icon: &[u8], mask: &[u8]) -> Vec<u8> {
+        let len = pixels / 8;
+        let mut data = vec![0; len * 2];
+        data[..icon.len()].copy_from_slice(icon);
+        data[len..len + mask.len()].copy_from_slice(mask);
+        data
+    }
+
+    /// An icon of `len` bytes starting with the given bytes.
+    fn indexed(len: usize, start: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; len];
+        data[..start.len()].copy_from_slice(start);
+        data
+    }
+
+    fn pixel(image: &Image, i: usize) -> [u8; 4] {
+        image.pixels[i * 4..i * 4 + 4].try_into().unwrap()
+    }
+
+    fn icns(members: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let len: usize = 8 + members.iter().map(|(_, data)| 8 + data.len()).sum::<usize>();
+        let mut icns = b"icns".to_vec();
+        icns.extend((len as u32).to_be_bytes());
+        for (res_type, data) in members {
+            icns.extend(*res_type);
+            icns.extend((8 + data.len() as u32).to_be_bytes());
+            icns.extend(*data);
+        }
+        icns
+    }
+
+    /// The channels of a 16 by 16 `is32` member, each compressed with runs:
+    /// red is 1, 2, 3 and then 0x10, green is all 0x20 and blue all 0x30.
+    fn is32_rle() -> Vec<u8> {
+        [
+            &[0x02, 1, 2, 3, 0xff, 0x10, 0xf8, 0x10][..],
+            &[0xff, 0x20, 0xfb, 0x20],
+            &[0xff, 0x30, 0xfb, 0x30],
+        ].concat()
+    }
+
+    #[test]
+    fn renders_one_bit_icon_with_mask() {
+        let mut family = IconFamily::new();
+        family.insert(ICN, one_bit(1024, &[0x80], &[0xc0]));
+        let image = family.render(ICN).unwrap().unwrap();
+        assert_eq!((image.width(), image.height()), (32, 32));
+        assert_eq!(pixel(&image, 0), [0, 0, 0, 0xff]);
+        assert_eq!(pixel(&image, 1), [0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(pixel(&image, 2), [0xff, 0xff, 0xff, 0]);
+        assert_eq!(pixel(&image, 32), [0xff, 0xff, 0xff, 0]);
+    }
+
+    #[test]
+    fn renders_each_4_bit_color() {
+        let mut family = IconFamily::new();
+        let icl4 = ResType::new(*b"icl4");
+        family.insert(icl4, indexed(512, &[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]));
+        family.insert(ICN, one_bit(1024, &[], &[0xff, 0xff]));
+        let image = family.render(icl4).unwrap().unwrap();
+        for (i, [r, g, b]) in PALETTE_4.into_iter().enumerate() {
+            assert_eq!(pixel(&image, i), [r, g, b, 0xff], "index {i}");
+        }
+        assert_eq!(pixel(&image, 1), [0xfc, 0xf3, 0x05, 0xff]);
+        assert_eq!(pixel(&image, 15), [0, 0, 0, 0xff]);
+        // past the end of the mask
+        assert_eq!(pixel(&image, 16), [0xff, 0xff, 0xff, 0]);
+    }
+
+    #[test]
+    fn renders_each_8_bit_color() {
+        let mut family = IconFamily::new();
+        let icl8 = ResType::new(*b"icl8");
+        family.insert(icl8, (0..1024).map(|i| i as u8).collect());
+        // without an ICN# there is no mask, so everything is opaque
+        let image = family.render(icl8).unwrap().unwrap();
+        for (i, [r, g, b]) in palette_8().into_iter().enumerate() {
+            assert_eq!(pixel(&image, i), [r, g, b, 0xff], "index {i}");
+        }
+        for (i, rgb) in [
+            (0, [0xff, 0xff, 0xff]),
+            (5, [0xff, 0xff, 0]),
+            (214, [0, 0, 0x33]),
+            (215, [0xee, 0, 0]),
+            (234, [0, 0x11, 0]),
+            (235, [0, 0, 0xee]),
+            (245, [0xee, 0xee, 0xee]),
+            (255, [0, 0, 0]),
+        ] {
+            assert_eq!(pixel(&image, i)[..3], rgb, "index {i}");
+        }
+    }
+
+    #[test]
+    fn loads_family_from_resource_fork() {
+        let mut writer = ResourceForkWriter::new();
+        writer.insert(ResType::new(*b"ics#"), 128, one_bit(256, &[], &[0x80]));
+        writer.insert(ResType::new(*b"ics8"), 128, indexed(256, &[0x23]));
+        writer.insert(ResType::new(*b"icm#"), 128, one_bit(192, &[], &[0xff]));
+        writer.insert(ResType::new(*b"icm4"), 128, indexed(96, &[0x30]));
+        writer.insert(ICN, 129, one_bit(1024, &[], &[]));
+        writer.insert(ICNS, 128, icns(&[(b"ic08", b"not rendered")]));
+        let fork = writer.write(vec![]).unwrap();
+        let mut file = ResourceFile::open(ResourceFork(io::Cursor::new(fork))).unwrap();
+
+        let family = IconFamily::load(&mut file, 128).unwrap();
+        let mut types: Vec<String> = family.types().map(|res_type| res_type.to_string()).collect();
+        types.sort();
+        assert_eq!(types, ["ic08", "icm#", "icm4", "ics#", "ics8"]);
+        assert_eq!(family.render(ICN).unwrap(), None);
+        assert_eq!(family.render(ResType::new(*b"ic08")).unwrap(), None);
+
+        let ics8 = family.render(ResType::new(*b"ics8")).unwrap().unwrap();
+        assert_eq!((ics8.width(), ics8.height()), (16, 16));
+        let [r, g, b] = palette_8()[0x23];
+        assert_eq!(pixel(&ics8, 0), [r, g, b, 0xff]);
+        assert_eq!(pixel(&ics8, 1)[3], 0);
+
+        let icm4 = family.render(ResType::new(*b"icm4")).unwrap().unwrap();
+        assert_eq!((icm4.width(), icm4.height()), (16, 12));
+        let [r, g, b] = PALETTE_4[3];
+        assert_eq!(pixel(&icm4, 0), [r, g, b, 0xff]);
+        assert_eq!(pixel(&icm4, 1), [0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(pixel(&icm4, 16)[3], 0);
+    }
+
+    #[test]
+    fn renders_compressed_icns_member() {
+        let mask: Vec<u8> = (0..=255).collect();
+        let family = IconFamily::parse_icns(&icns(&[(b"is32", &is32_rle()), (b"s8mk", &mask)])).unwrap();
+        let image = family.render(IS32).unwrap().unwrap();
+        assert_eq!((image.width(), image.height()), (16, 16));
+        assert_eq!(pixel(&image, 0), [1, 0x20, 0x30, 0]);
+        assert_eq!(pixel(&image, 2), [3, 0x20, 0x30, 2]);
+        assert_eq!(pixel(&image, 3), [0x10, 0x20, 0x30, 3]);
+        assert_eq!(pixel(&image, 255), [0x10, 0x20, 0x30, 0xff]);
+    }
+
+    #[test]
+    fn rejects_truncated_runs() {
+        let rle = is32_rle();
+        let mut family = IconFamily::new();
+        family.insert(IS32, rle[..rle.len() - 1].to_vec());
+        assert!(matches!(family.render(IS32), Err(Error::InvalidResource { res_type: IS32 })));
+        assert_eq!(unpack_rle(&[0x02, 1, 2], 1), None);
+        assert_eq!(unpack_rle(&[0x80], 1), None);
+
+        let invalid = |icns: &[u8]| matches!(
+            IconFamily::parse_icns(icns),
+            Err(Error::InvalidResource { res_type: ICNS }),
+        );
+        let mut icns = icns(&[(b"is32", &rle)]);
+        let mut truncated = icns.clone();
+        truncated.truncate(icns.len() - 1);
+        assert!(invalid(&truncated));
+        // the members are whole, but the header claims more than there is
+        let len = icns.len() as u32 + 8;
+        icns[4..8].copy_from_slice(&len.to_be_bytes());
+        assert!(invalid(&icns));
+        for len in [0u32, 7] {
+            icns[4..8].copy_from_slice(&len.to_be_bytes());
+            assert!(invalid(&icns));
+        }
+        assert!(invalid(b"icns"));
+    }
+
+    #[test]
+    fn ignores_bytes_past_icns_length() {
+        let mut icns = icns(&[(b"is32", &is32_rle())]);
+        icns.extend(b"s8mk\x00\x00\x00\x09!");
+        let family = IconFamily::parse_icns(&icns).unwrap();
+        assert_eq!(family.types().collect::<Vec<_>>(), [IS32]);
+    }
+
+    #[test]
+    fn picks_most_detailed_member() {
+        let mut family = IconFamily::new();
+        family.insert(ResType::new(*b"ics#"), one_bit(256, &[], &[]));
+        assert_eq!(family.best().unwrap().unwrap().width(), 16);
+        family.insert(ICN, one_bit(1024, &[], &[]));
+        family.insert(ResType::new(*b"icl4"), indexed(512, &[0x10]));
+        let best = family.best().unwrap().unwrap();
+        assert_eq!(best, family.render(ResType::new(*b"icl4")).unwrap().unwrap());
+        assert_eq!(best.width(), 32);
+
+        let mut family = IconFamily::new();
+        family.insert(ResType::new(*b"ic08"), b"not rendered".to_vec());
+        assert_eq!(family.best().unwrap(), None);
+    }
+
+    #[test]
+    fn writes_png() {
+        let mut family = IconFamily::new();
+        family.insert(ICN, one_bit(1024, &[0x80], &[0xc0]));
+        let image = family.render(ICN).unwrap().unwrap();
+        let mut png = vec![];
+        image.write_png(&mut png).unwrap();
+
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height), (32, 32));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(pixels, image.pixels());
+    }
+
+    #[test]
+    fn renders_archive_icons() {
+        let icon = one_bit(1024, &[0x80], &[0xc0]);
+        let start = 26 + 12;
+        let file = testing::raw(APPLESINGLE_MAGIC, None, &[(EntryType::IconBW, start, 256)], &icon);
+        let mut archive = applesingle::parse_seekable(io::Cursor::new(file)).unwrap();
+        let mut family = IconFamily::new();
+        family.insert(ICN, icon);
+        assert_eq!(archive_icon(&mut archive).unwrap(), family.render(ICN).unwrap());
+
+        let cicn = color_icon(4, 4, 1);
+        let len = cicn.len() as u32;
+        let file = testing::raw(APPLESINGLE_MAGIC, None, &[
+            (EntryType::IconBW, start + 12, 256),
+            (EntryType::IconColor, start + 12 + 256, len),
+        ], &[family.get(ICN).unwrap(), &cicn].concat());
+        let mut archive = applesingle::parse_seekable(io::Cursor::new(file)).unwrap();
+        assert_eq!(archive_icon(&mut archive).unwrap(), Some(render_color_icon(&cicn).unwrap()));
+
+        let file = testing::raw(APPLESINGLE_MAGIC, None, &[(EntryType::RealName, start, 4)], b"name");
+        let mut archive = applesingle::parse_seekable(io::Cursor::new(file)).unwrap();
+        assert_eq!(archive_icon(&mut archive).unwrap(), None);
+    }
+
+    /// Builds a `cicn` with an 8-bit pixmap of `width` by 2 pixels and a
+    /// two-color table, with the given row lengths for the pixmap and mask.
+    fn color_icon(width: i16, row_bytes: u16, mask_row_bytes: u16) -> Vec<u8> {
+        let bounds = |data: &mut Vec<u8>| {
+            for value in [0, 0, 2, width] {
+                data.extend(value.to_be_bytes());
+            }
+        };
+        let mut data = vec![0; 4];
+        data.extend((0x8000 | row_bytes).to_be_bytes());
+        bounds(&mut data);
+        data.extend([0; 18]);
+        data.extend(8u16.to_be_bytes());
+        data.extend([0; 16]);
+        // the mask, then an empty 1-bit icon
+        data.extend([0; 4]);
+        data.extend(mask_row_bytes.to_be_bytes());
+        bounds(&mut data);
+        data.extend([0; 14]);
+        data.extend([0; 4]);
+        data.extend(vec![0xff; mask_row_bytes as usize * 2]);
+        // a color table with black and white
+        data.extend([0; 6]);
+        data.extend(1u16.to_be_bytes());
+        data.extend([0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        data.extend([0, 1, 0, 0, 0, 0, 0, 0]);
+        data.extend(vec![1; row_bytes as usize * 2]);
+        data
+    }
+
+    #[test]
+    fn renders_color_icon() {
+        let image = render_color_icon(&color_icon(4, 4, 1)).unwrap();
+        assert_eq!((image.width, image.height), (4, 2));
+        assert_eq!(image.pixels, [0, 0, 0, 0xff].repeat(8));
+    }
+
+    #[test]
+    fn rejects_mask_narrower_than_icon() {
+        assert!(matches!(
+            render_color_icon(&color_icon(32, 32, 1)),
+            Err(Error::InvalidResource { res_type: CICN }),
+        ));
+    }
+
+    #[test]
+    fn renders_empty_color_icon() {
+        let image = render_color_icon(&color_icon(0, 0, 0)).unwrap();
+        assert_eq!((image.width, image.height), (0, 2));
+        assert!(image.pixels.is_empty());
+    }
+}
@@ -0,0 +1,458 @@
+//! Decoders for resources which hold text.
+
+use std::fmt;
+
+use deku::prelude::*;
+
+use super::{
+    ResType,
+    ResourceData,
+};
+use crate::error;
+
+/// The characters which Mac OS Roman places above ASCII.
+const MAC_ROMAN: [char; 128] = [
+    '\u{00C4}', '\u{00C5}', '\u{00C7}', '\u{00C9}', '\u{00D1}', '\u{00D6}', '\u{00DC}', '\u{00E1}',
+    '\u{00E0}', '\u{00E2}', '\u{00E4}', '\u{00E3}', '\u{00E5}', '\u{00E7}', '\u{00E9}', '\u{00E8}',
+    '\u{00EA}', '\u{00EB}', '\u{00ED}', '\u{00EC}', '\u{00EE}', '\u{00EF}', '\u{00F1}', '\u{00F3}',
+    '\u{00F2}', '\u{00F4}', '\u{00F6}', '\u{00F5}', '\u{00FA}', '\u{00F9}', '\u{00FB}', '\u{00FC}',
+    '\u{2020}', '\u{00B0}', '\u{00A2}', '\u{00A3}', '\u{00A7}', '\u{2022}', '\u{00B6}', '\u{00DF}',
+    '\u{00AE}', '\u{00A9}', '\u{2122}', '\u{00B4}', '\u{00A8}', '\u{2260}', '\u{00C6}', '\u{00D8}',
+    '\u{221E}', '\u{00B1}', '\u{2264}', '\u{2265}', '\u{00A5}', '\u{00B5}', '\u{2202}', '\u{2211}',
+    '\u{220F}', '\u{03C0}', '\u{222B}', '\u{00AA}', '\u{00BA}', '\u{03A9}', '\u{00E6}', '\u{00F8}',
+    '\u{00BF}', '\u{00A1}', '\u{00AC}', '\u{221A}', '\u{0192}', '\u{2248}', '\u{2206}', '\u{00AB}',
+    '\u{00BB}', '\u{2026}', '\u{00A0}', '\u{00C0}', '\u{00C3}', '\u{00D5}', '\u{0152}', '\u{0153}',
+    '\u{2013}', '\u{2014}', '\u{201C}', '\u{201D}', '\u{2018}', '\u{2019}', '\u{00F7}', '\u{25CA}',
+    '\u{00FF}', '\u{0178}', '\u{2044}', '\u{20AC}', '\u{2039}', '\u{203A}', '\u{FB01}', '\u{FB02}',
+    '\u{2021}', '\u{00B7}', '\u{201A}', '\u{201E}', '\u{2030}', '\u{00C2}', '\u{00CA}', '\u{00C1}',
+    '\u{00CB}', '\u{00C8}', '\u{00CD}', '\u{00CE}', '\u{00CF}', '\u{00CC}', '\u{00D3}', '\u{00D4}',
+    '\u{F8FF}', '\u{00D2}', '\u{00DA}', '\u{00DB}', '\u{00D9}', '\u{0131}', '\u{02C6}', '\u{02DC}',
+    '\u{00AF}', '\u{02D8}', '\u{02D9}', '\u{02DA}', '\u{00B8}', '\u{02DD}', '\u{02DB}', '\u{02C7}',
+];
+
+/// Converts text from Mac OS Roman, the encoding used by resources on
+/// western systems. Line breaks are left as carriage returns.
+pub fn mac_roman(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|&b| match b {
+            0..=0x7f => b as char,
+            _ => MAC_ROMAN[b as usize - 0x80],
+        })
+        .collect()
+}
+
+/// Converts text into Mac OS Roman, failing if any character is missing from
+/// it.
+pub fn to_mac_roman(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| match c {
+            '\0'..='\x7f' => Some(c as u8),
+            _ => MAC_ROMAN.iter()
+                .position(|m| *m == c)
+                .map(|i| i as u8 + 0x80),
+        })
+        .collect()
+}
+
+/// A string of at most 255 bytes, preceded by its length. On its own it is
+/// the contents of a `STR ` resource.
+#[derive(DekuRead, DekuWrite, Clone, Default, PartialEq, Eq)]
+pub struct PascalString {
+    len: u8,
+    #[deku(count = "len")]
+    bytes: Vec<u8>,
+}
+
+impl PascalString {
+    /// Fails if `bytes` is too long to be counted by a single byte.
+    pub fn new(bytes: Vec<u8>) -> Option<Self> {
+        let len = bytes.len().try_into().ok()?;
+        Some(Self { len, bytes })
+    }
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl fmt::Display for PascalString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", mac_roman(&self.bytes))
+    }
+}
+
+impl fmt::Debug for PascalString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PascalString({:?})", mac_roman(&self.bytes))
+    }
+}
+
+impl ResourceData for PascalString {
+    const TYPE: ResType = ResType::new(*b"STR ");
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        Ok(Self::from_bytes((data, 0))?.1)
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        Ok(self.to_bytes()?)
+    }
+}
+
+/// The contents of a `STR#` resource.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Default, PartialEq, Eq)]
+pub struct StringList {
+    #[deku(endian = "big")]
+    count: u16,
+    #[deku(count = "count")]
+    strings: Vec<PascalString>,
+}
+
+impl StringList {
+    /// Fails if there are more strings than can be counted.
+    pub fn new(strings: Vec<PascalString>) -> Option<Self> {
+        let count = strings.len().try_into().ok()?;
+        Some(Self { count, strings })
+    }
+    pub fn strings(&self) -> &[PascalString] {
+        &self.strings
+    }
+    /// Finds a string by its index, counting from 1 as `GetIndString` does.
+    pub fn get(&self, index: usize) -> Option<&PascalString> {
+        self.strings.get(index.checked_sub(1)?)
+    }
+}
+
+impl ResourceData for StringList {
+    const TYPE: ResType = ResType::new(*b"STR#");
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        Ok(Self::from_bytes((data, 0))?.1)
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        Ok(self.to_bytes()?)
+    }
+}
+
+/// How finished a version of a program is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReleaseStage {
+    Development,
+    Alpha,
+    Beta,
+    Final,
+}
+
+impl ReleaseStage {
+    fn from_u8(stage: u8) -> Option<Self> {
+        match stage {
+            0x20 => Some(Self::Development),
+            0x40 => Some(Self::Alpha),
+            0x60 => Some(Self::Beta),
+            0x80 => Some(Self::Final),
+            _ => None,
+        }
+    }
+}
+
+/// The contents of a `vers` resource, as shown in the Finder's Get Info
+/// window. The version numbers are stored in binary-coded decimal, and are
+/// displayed the way the Finder shows them, like "1.0.1b2".
+#[derive(Debug, DekuRead, DekuWrite, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u8,
+    /// The minor version in the high nibble and the bug fix version in the
+    /// low nibble.
+    pub minor: u8,
+    pub stage: u8,
+    pub non_release: u8,
+    #[deku(endian = "big")]
+    pub region: u16,
+    /// Just the version number, such as "1.0.1".
+    pub short_version: PascalString,
+    /// The version number with a copyright notice or other description.
+    pub long_version: PascalString,
+}
+
+impl Version {
+    pub fn release_stage(&self) -> Option<ReleaseStage> {
+        ReleaseStage::from_u8(self.stage)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:x}.{:x}", self.major, self.minor >> 4)?;
+        if self.minor & 0xf != 0 {
+            write!(f, ".{:x}", self.minor & 0xf)?;
+        }
+        match self.release_stage() {
+            Some(ReleaseStage::Development) => write!(f, "d{:x}", self.non_release),
+            Some(ReleaseStage::Alpha) => write!(f, "a{:x}", self.non_release),
+            Some(ReleaseStage::Beta) => write!(f, "b{:x}", self.non_release),
+            Some(ReleaseStage::Final) | None => Ok(()),
+        }
+    }
+}
+
+impl ResourceData for Version {
+    const TYPE: ResType = ResType::new(*b"vers");
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        Ok(Self::from_bytes((data, 0))?.1)
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        Ok(self.to_bytes()?)
+    }
+}
+
+/// The contents of a `TEXT` resource, which may be styled by a `styl`
+/// resource with the same ID.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Text(pub Vec<u8>);
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", mac_roman(&self.0))
+    }
+}
+
+impl fmt::Debug for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Text({:?})", mac_roman(&self.0))
+    }
+}
+
+impl ResourceData for Text {
+    const TYPE: ResType = ResType::new(*b"TEXT");
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        Ok(Self(data.to_vec()))
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// The styles a font can be drawn in.
+#[derive(DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct FontStyle {
+    #[deku(bits = "1", pad_bits_before = "1")]
+    pub extend: bool,
+    #[deku(bits = "1")]
+    pub condense: bool,
+    #[deku(bits = "1")]
+    pub shadow: bool,
+    #[deku(bits = "1")]
+    pub outline: bool,
+    #[deku(bits = "1")]
+    pub underline: bool,
+    #[deku(bits = "1")]
+    pub italic: bool,
+    #[deku(bits = "1")]
+    pub bold: bool,
+}
+
+impl fmt::Display for FontStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = vec![];
+        if self.bold {
+            text.push("BOLD");
+        }
+        if self.italic {
+            text.push("ITALIC");
+        }
+        if self.underline {
+            text.push("UNDERLINE");
+        }
+        if self.outline {
+            text.push("OUTLINE");
+        }
+        if self.shadow {
+            text.push("SHADOW");
+        }
+        if self.condense {
+            text.push("CONDENSE");
+        }
+        if self.extend {
+            text.push("EXTEND");
+        }
+        write!(f, "{}", text.join("|"))
+    }
+}
+
+impl fmt::Debug for FontStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FontStyle({})", self)
+    }
+}
+
+#[derive(Debug, DekuRead, DekuWrite, Default, Clone, Copy, PartialEq, Eq)]
+#[deku(endian = "big")]
+pub struct RgbColor {
+    pub red: u16,
+    pub green: u16,
+    pub blue: u16,
+}
+
+/// The style of the text from `start` up to the start of the next run.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Copy, PartialEq, Eq)]
+pub struct StyleRun {
+    #[deku(endian = "big")]
+    pub start: u32,
+    #[deku(endian = "big")]
+    pub line_height: i16,
+    #[deku(endian = "big")]
+    pub ascent: i16,
+    #[deku(endian = "big")]
+    pub font: i16,
+    #[deku(pad_bytes_after = "1")]
+    pub face: FontStyle,
+    #[deku(endian = "big")]
+    pub size: i16,
+    pub color: RgbColor,
+}
+
+/// The contents of a `styl` resource.
+#[derive(Debug, DekuRead, DekuWrite, Clone, Default, PartialEq, Eq)]
+pub struct Styles {
+    #[deku(endian = "big")]
+    count: u16,
+    #[deku(count = "count")]
+    runs: Vec<StyleRun>,
+}
+
+impl Styles {
+    /// Fails if there are more runs than can be counted.
+    pub fn new(runs: Vec<StyleRun>) -> Option<Self> {
+        let count = runs.len().try_into().ok()?;
+        Some(Self { count, runs })
+    }
+    pub fn runs(&self) -> &[StyleRun] {
+        &self.runs
+    }
+    /// Splits `text` into the pieces covered by each run.
+    pub fn apply<'a>(&'a self, text: &'a Text) -> impl Iterator<Item = (&'a StyleRun, &'a [u8])> {
+        let len = text.0.len();
+        self.runs.iter()
+            .enumerate()
+            .map(move |(i, run)| {
+                let end = self.runs.get(i + 1)
+                    .map_or(len, |next| (next.start as usize).min(len));
+                let start = (run.start as usize).min(end);
+                (run, &text.0[start..end])
+            })
+    }
+}
+
+impl ResourceData for Styles {
+    const TYPE: ResType = ResType::new(*b"styl");
+    fn decode(data: &[u8]) -> error::Result<Self> {
+        Ok(Self::from_bytes((data, 0))?.1)
+    }
+    fn encode(&self) -> error::Result<Vec<u8>> {
+        Ok(self.to_bytes()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_mac_roman() {
+        assert_eq!(mac_roman(b"Caf\x8e \xa9 \xd2hi\xd3\r"), "Caf\u{e9} \u{a9} \u{201c}hi\u{201d}\r");
+        assert_eq!(mac_roman(b"\x80\xf0\xff"), "\u{c4}\u{f8ff}\u{2c7}");
+        assert_eq!(to_mac_roman("Caf\u{e9} \u{2122}").unwrap(), b"Caf\x8e \xaa");
+        assert_eq!(to_mac_roman("\u{4e2d}"), None);
+        let all: Vec<u8> = (0..=255).collect();
+        assert_eq!(to_mac_roman(&mac_roman(&all)).unwrap(), all);
+    }
+
+    #[test]
+    fn decodes_strings() {
+        let string = PascalString::decode(b"\x05Hello, ignored").unwrap();
+        assert_eq!(string.bytes(), b"Hello");
+        assert_eq!(string.encode().unwrap(), b"\x05Hello");
+        assert!(PascalString::decode(b"\x05Hell").is_err());
+
+        let data = b"\x00\x03\x03One\x00\x05Th\x8ere";
+        let list = StringList::decode(data).unwrap();
+        assert_eq!(list.strings().len(), 3);
+        assert_eq!(list.get(1).unwrap().bytes(), b"One");
+        assert_eq!(list.get(2).unwrap().bytes(), b"");
+        assert_eq!(list.get(3).unwrap().to_string(), "Th\u{e9}re");
+        assert_eq!(list.get(0), None);
+        assert_eq!(list.get(4), None);
+        assert_eq!(list.encode().unwrap(), data);
+    }
+
+    #[test]
+    fn rejects_truncated_string_list() {
+        // the count promises a third string which is missing.
+        assert!(StringList::decode(b"\x00\x03\x03One\x00").is_err());
+        // the last string is shorter than its length byte says.
+        assert!(StringList::decode(b"\x00\x02\x03One\x05Two").is_err());
+    }
+
+    #[test]
+    fn decodes_version() {
+        let data = b"\x01\x12\x60\x02\x00\x00\x051.1.2\x0d1.1.2 \xa9 Apple";
+        let version = Version::decode(data).unwrap();
+        assert_eq!(version.major, 1);
+        assert_eq!(version.minor, 0x12);
+        assert_eq!(version.release_stage(), Some(ReleaseStage::Beta));
+        assert_eq!(version.region, 0);
+        assert_eq!(version.short_version.bytes(), b"1.1.2");
+        assert_eq!(version.long_version.to_string(), "1.1.2 \u{a9} Apple");
+        assert_eq!(version.to_string(), "1.1.2b2");
+        assert_eq!(version.encode().unwrap(), data);
+
+        let version = Version::decode(b"\x02\x50\x80\x00\x00\x0e\x00\x00").unwrap();
+        assert_eq!(version.release_stage(), Some(ReleaseStage::Final));
+        assert_eq!(version.region, 14);
+        assert_eq!(version.to_string(), "2.5");
+        assert!(Version::decode(b"\x02\x50\x80\x00\x00\x0e\x03").is_err());
+    }
+
+    #[test]
+    fn decodes_text() {
+        let text = Text::decode(b"Line\rNext \xa5").unwrap();
+        assert_eq!(text.to_string(), "Line\rNext \u{2022}");
+        assert_eq!(text.encode().unwrap(), b"Line\rNext \xa5");
+    }
+
+    #[test]
+    fn decodes_styles() {
+        let data = [
+            b"\x00\x02".as_slice(),
+            // plain 12 point Geneva in black from the start.
+            b"\x00\x00\x00\x00\x00\x10\x00\x0c\x00\x03\x00\x00\x00\x0c\x00\x00\x00\x00\x00\x00",
+            // bold italic 24 point Times in red from byte 4.
+            b"\x00\x00\x00\x04\x00\x20\x00\x18\x00\x14\x03\x00\x00\x18\xff\xff\x00\x00\x00\x00",
+        ].concat();
+        let styles = Styles::decode(&data).unwrap();
+        let [plain, fancy] = styles.runs() else { panic!("expected two runs") };
+        assert_eq!((plain.start, plain.line_height, plain.ascent, plain.font, plain.size), (0, 16, 12, 3, 12));
+        assert_eq!(plain.face, FontStyle::default());
+        assert_eq!(plain.color, RgbColor::default());
+        assert_eq!((fancy.start, fancy.font, fancy.size), (4, 20, 24));
+        assert_eq!(fancy.face, FontStyle { bold: true, italic: true, ..Default::default() });
+        assert_eq!(fancy.face.to_string(), "BOLD|ITALIC");
+        assert_eq!(fancy.color, RgbColor { red: 0xffff, green: 0, blue: 0 });
+        assert_eq!(styles.encode().unwrap(), data);
+
+        let text = Text(b"Big red".to_vec());
+        let pieces: Vec<&[u8]> = styles.apply(&text).map(|(_, piece)| piece).collect();
+        assert_eq!(pieces, [b"Big ".as_slice(), b"red"]);
+        // a run starting past the end of the text covers nothing.
+        let text = Text(b"Bi".to_vec());
+        let pieces: Vec<&[u8]> = styles.apply(&text).map(|(_, piece)| piece).collect();
+        assert_eq!(pieces, [b"Bi".as_slice(), b""]);
+    }
+
+    #[test]
+    fn rejects_styles_past_end() {
+        let data = [
+            b"\x00\x02".as_slice(),
+            b"\x00\x00\x00\x00\x00\x10\x00\x0c\x00\x03\x00\x00\x00\x0c\x00\x00\x00\x00\x00\x00",
+        ].concat();
+        assert!(Styles::decode(&data).is_err());
+        assert!(Styles::decode(&data[..12]).is_err());
+    }
+}
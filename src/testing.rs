@@ -0,0 +1,68 @@
+//! Helpers shared by the tests of several modules.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::applesingle::{APPLEDOUBLE_MAGIC, EntryType};
+
+/// An empty folder for `test` to work in.
+pub(crate) fn scratch(test: &str) -> PathBuf {
+    let path = std::env::temp_dir()
+        .join(format!("forkcordion-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Whether the file system holding `path` keeps user extended attributes,
+/// which not all of them do.
+#[cfg(unix)]
+pub(crate) fn supports_xattrs(path: &Path) -> bool {
+    match ::xattr::set(path, "user.forkcordion.test", b"") {
+        Err(e) if e.kind() == std::io::ErrorKind::Unsupported => false,
+        result => {
+            result.unwrap();
+            true
+        },
+    }
+}
+
+/// Lays out an AppleSingle or AppleDouble file, as told by `magic`, with the
+/// given `(id, offset, len)` entries followed by `body`. The entries may
+/// point anywhere. Only version 1 files have a home file system.
+pub(crate) fn raw(
+    magic: u32,
+    home_file_system: Option<&[u8; 16]>,
+    segments: &[(EntryType, u32, u32)],
+    body: &[u8],
+) -> Vec<u8> {
+    let version: u32 = match home_file_system {
+        Some(_) => 0x0001_0000,
+        None => 0x0002_0000,
+    };
+    let mut bytes = magic.to_be_bytes().to_vec();
+    bytes.extend(version.to_be_bytes());
+    bytes.extend(home_file_system.unwrap_or(&[0; 16]));
+    bytes.extend((segments.len() as u16).to_be_bytes());
+    for &(id, offset, len) in segments {
+        bytes.extend([u32::from(id), offset, len].map(u32::to_be_bytes).concat());
+    }
+    bytes.extend_from_slice(body);
+    bytes
+}
+
+/// An AppleDouble header file holding the given entries one after another,
+/// as macOS writes them.
+pub(crate) fn apple_double(entries: &[(EntryType, &[u8])]) -> Vec<u8> {
+    let mut offset = 26 + 12 * entries.len() as u32;
+    let mut segments = vec![];
+    let mut body = vec![];
+    for &(id, contents) in entries {
+        segments.push((id, offset, contents.len() as u32));
+        offset += contents.len() as u32;
+        body.extend_from_slice(contents);
+    }
+    raw(APPLEDOUBLE_MAGIC, None, &segments, &body)
+}
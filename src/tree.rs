@@ -0,0 +1,374 @@
+//! Unpacking whole folders of archives onto the file system, and packing
+//! them back up again.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{
+    MultiForkArchive,
+    appledouble::{self, AppleDoubleWriter, Layout},
+    applesingle,
+    basilisk,
+    error::{self, Error},
+    format::Format,
+    loose,
+    rsrc::mac_roman,
+};
+
+/// How the forks and Finder info of an unpacked file are kept on the file
+/// system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Representation {
+    /// In the file's extended attributes. See [`crate::xattr`].
+    #[cfg(unix)]
+    ExtendedAttributes,
+    /// In an AppleDouble header file, wherever `Layout` keeps it.
+    AppleDouble(Layout),
+    /// The resource fork alone, in a file of the same name with `.rsrc`
+    /// appended. The Finder info is lost.
+    RsrcFile,
+    /// In `.rsrc` and `.finf` folders, as Basilisk II does. See
+    /// [`crate::basilisk`].
+    Basilisk,
+}
+
+impl Representation {
+    /// Opens the file at `path` along with the forks and Finder info kept
+    /// beside it. Unless what is kept beside it says otherwise, the archive
+    /// is named after the file and dated from its metadata.
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> error::Result<Box<dyn MultiForkArchive>> {
+        let path = path.as_ref();
+        Ok(match self {
+            #[cfg(unix)]
+            Self::ExtendedAttributes => Box::new(crate::xattr::open(path)?),
+            Self::AppleDouble(_) => match appledouble::find_header(path) {
+                Some(_) => Box::new(appledouble::open(path)?),
+                None => Box::new(loose::assemble(path, vec![], None, Format::AppleDouble)?),
+            },
+            Self::RsrcFile => {
+                let rsrc = match rsrc_file_path(path).map(fs::read) {
+                    Some(Ok(rsrc)) => rsrc,
+                    Some(Err(e)) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => vec![],
+                };
+                Box::new(loose::assemble(path, rsrc, None, Format::RsrcFile)?)
+            },
+            Self::Basilisk => Box::new(basilisk::open(path)?),
+        })
+    }
+    /// Writes the data fork of `archive` to a file at `path`, keeping its
+    /// resource fork and Finder info beside it.
+    pub fn extract<A: MultiForkArchive + ?Sized, P: AsRef<Path>>(
+        &self,
+        archive: &mut A,
+        path: P,
+    ) -> error::Result<()> {
+        let path = path.as_ref();
+        match self {
+            #[cfg(unix)]
+            Self::ExtendedAttributes => crate::xattr::extract(archive, path),
+            Self::AppleDouble(layout) => loose::extract(archive, path, |archive, rsrc| {
+                let mut writer = AppleDoubleWriter::new(archive)?;
+                if !rsrc.is_empty() {
+                    let len = rsrc.len() as u64;
+                    writer.rsrc_fork(io::Cursor::new(rsrc), Some(len));
+                }
+                Ok(writer.save_header(path, *layout)?)
+            }),
+            Self::RsrcFile => loose::extract(archive, path, |_, rsrc| {
+                if !rsrc.is_empty() {
+                    let rsrc_path = rsrc_file_path(path)
+                        .ok_or(io::Error::from(io::ErrorKind::InvalidInput))?;
+                    fs::write(rsrc_path, rsrc)?;
+                }
+                Ok(())
+            }),
+            Self::Basilisk => basilisk::extract(archive, path),
+        }
+    }
+    /// Whether the file at `path` only holds part of another file, and so
+    /// should not be packed by itself.
+    fn is_companion(&self, path: &Path) -> bool {
+        match self {
+            Self::RsrcFile => {
+                path.extension().is_some_and(|extension| extension == "rsrc")
+                    && path.with_extension("").is_file()
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Finds the path of the file holding the resource fork of the file at
+/// `path` in the [`Representation::RsrcFile`] representation.
+pub fn rsrc_file_path<P: AsRef<Path>>(path: P) -> Option<PathBuf> {
+    let path = path.as_ref();
+    let mut name = OsString::from(path.file_name()?);
+    name.push(".rsrc");
+    Some(path.with_file_name(name))
+}
+
+/// What became of one file in a tree.
+#[derive(Debug)]
+pub struct Outcome {
+    pub source: PathBuf,
+    /// Where the file was written.
+    pub result: error::Result<PathBuf>,
+}
+
+/// Lists the files beneath `root`, relative to it, leaving out hidden files
+/// and folders, which hold the forks of other files in most
+/// representations.
+fn walk(root: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut folders = vec![PathBuf::new()];
+    while let Some(folder) = folders.pop() {
+        let mut entries = fs::read_dir(root.join(&folder))?
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            if entry.file_name().as_encoded_bytes().starts_with(b".") {
+                continue;
+            }
+            let relative = folder.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                folders.push(relative);
+            } else {
+                files.push(relative);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Creates `path` for writing, refusing to replace anything already there.
+fn claim(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata().is_ok() {
+        return Err(io::ErrorKind::AlreadyExists.into());
+    }
+    match path.parent() {
+        Some(parent) => fs::create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
+/// Extracts every AppleSingle, MacBinary and BinHex file beneath `source`
+/// into the same place beneath `target`, named as the archive names it and
+/// keeping the forks in `representation`. Files which cannot be extracted
+/// are reported rather than stopping the rest.
+pub fn unpack<S: AsRef<Path>, T: AsRef<Path>>(
+    source: S,
+    target: T,
+    representation: Representation,
+) -> io::Result<Vec<Outcome>> {
+    let (source, target) = (source.as_ref(), target.as_ref());
+    let outcomes = walk(source)?
+        .into_iter()
+        .map(|relative| {
+            let source = source.join(&relative);
+            let result = unpack_file(&source, target.join(&relative), representation);
+            Outcome { source, result }
+        })
+        .collect();
+    Ok(outcomes)
+}
+
+fn unpack_file(
+    source: &Path,
+    target: PathBuf,
+    representation: Representation,
+) -> error::Result<PathBuf> {
+    let mut archive = crate::open(File::open(source)?)?;
+    if archive.format() == Format::AppleDouble {
+        // A header file means nothing without its data file.
+        return Err(Error::UnrecognizedFormat);
+    }
+    let name = archive.name()
+        .map(|name| mac_roman(name.as_bytes()).replace('/', ":"))
+        .filter(|name| !name.is_empty() && name != "." && name != "..")
+        .map(OsString::from)
+        .or_else(|| target.file_stem().map(OsString::from))
+        .ok_or(Error::Incomplete)?;
+    let path = target.with_file_name(name);
+    claim(&path)?;
+    representation.extract(&mut archive, &path)?;
+    Ok(path)
+}
+
+/// Packs every file beneath `source`, along with the forks kept beside it in
+/// `representation`, into an AppleSingle file in the same place beneath
+/// `target`, with `.as` appended to its name. Files which cannot be packed
+/// are reported rather than stopping the rest.
+pub fn pack<S: AsRef<Path>, T: AsRef<Path>>(
+    source: S,
+    target: T,
+    representation: Representation,
+) -> io::Result<Vec<Outcome>> {
+    let (source, target) = (source.as_ref(), target.as_ref());
+    let outcomes = walk(source)?
+        .into_iter()
+        .filter(|relative| !representation.is_companion(&source.join(relative)))
+        .map(|relative| {
+            let source = source.join(&relative);
+            let result = pack_file(&source, target.join(&relative), representation);
+            Outcome { source, result }
+        })
+        .collect();
+    Ok(outcomes)
+}
+
+fn pack_file(
+    source: &Path,
+    target: PathBuf,
+    representation: Representation,
+) -> error::Result<PathBuf> {
+    let mut archive = representation.open(source)?;
+    let mut name = target.file_name()
+        .ok_or(Error::Incomplete)?
+        .to_os_string();
+    name.push(".as");
+    let path = target.with_file_name(name);
+    claim(&path)?;
+    applesingle::repack(&mut *archive, File::create(&path)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::{
+        FileType,
+        applesingle::EntryType,
+        testing::{apple_double, scratch},
+    };
+
+    fn touch(path: &Path, contents: &[u8]) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn walks_visible_files() {
+        let root = scratch("walk");
+        for file in ["b", "a/c", "a/d/e", ".hidden", "._b", ".AppleDouble/b", "a/.f"] {
+            touch(&root.join(file), b"");
+        }
+        let files = walk(&root).unwrap();
+        assert_eq!(files, [Path::new("a/c"), Path::new("a/d/e"), Path::new("b")]);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn claims_only_free_paths() {
+        let root = scratch("claim");
+        let path = root.join("new/deep/file");
+        claim(&path).unwrap();
+        assert!(path.parent().unwrap().is_dir());
+        assert!(!path.exists());
+
+        touch(&path, b"taken");
+        assert_eq!(claim(&path).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(claim(path.parent().unwrap()).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&path).unwrap(), b"taken");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn packs_rsrc_files_with_their_data_file() {
+        let (source, target) = (scratch("companion-source"), scratch("companion-target"));
+        touch(&source.join("App"), b"data");
+        touch(&source.join("App.rsrc"), b"rsrc");
+        touch(&source.join("Lone.rsrc"), b"lone");
+
+        let outcomes = pack(&source, &target, Representation::RsrcFile).unwrap();
+        let sources: Vec<&Path> = outcomes.iter().map(|outcome| outcome.source.as_path()).collect();
+        assert_eq!(sources, [source.join("App"), source.join("Lone.rsrc")]);
+        assert!(outcomes.iter().all(|outcome| outcome.result.is_ok()));
+
+        let mut archive = crate::open(File::open(target.join("App.as")).unwrap()).unwrap();
+        assert_eq!(loose::read_rsrc_fork(&mut archive).unwrap(), b"rsrc");
+        fs::remove_dir_all(source).unwrap();
+        fs::remove_dir_all(target).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_applesingle() {
+        let (source, packed, unpacked) = (scratch("trip-source"), scratch("trip-packed"), scratch("trip-unpacked"));
+        let mut finder_info = [0; 32];
+        finder_info[..8].copy_from_slice(b"TEXTttxt");
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        touch(&source.join("Docs/Read Me"), b"hello");
+        touch(&source.join("Docs/._Read Me"), &apple_double(&[
+            (EntryType::FinderInfo, &finder_info),
+            (EntryType::ResourceFork, b"resources"),
+        ]));
+        File::options().write(true).open(source.join("Docs/Read Me")).unwrap()
+            .set_modified(modified).unwrap();
+
+        let representation = Representation::AppleDouble(Layout::DotUnderscore);
+        let outcomes = pack(&source, &packed, representation).unwrap();
+        assert_eq!(outcomes.len(), 1);
+        let path = outcomes.into_iter().next().unwrap().result.unwrap();
+        assert_eq!(path, packed.join("Docs/Read Me.as"));
+        let archive = crate::open(File::open(&path).unwrap()).unwrap();
+        // the header file has neither, so they come from the data file.
+        assert_eq!(archive.name().unwrap().as_bytes(), b"Read Me");
+        assert_eq!(archive.dates().unwrap().modify.to_system_time(), Some(modified));
+
+        let outcomes = unpack(&packed, &unpacked, representation).unwrap();
+        let path = outcomes.into_iter().next().unwrap().result.unwrap();
+        assert_eq!(path, unpacked.join("Docs/Read Me"));
+        assert_eq!(fs::read(&path).unwrap(), b"hello");
+        assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+        let mut archive = representation.open(&path).unwrap();
+        assert_eq!(archive.finder_info().unwrap().file_type, FileType::from(*b"TEXT"));
+        assert_eq!(loose::read_rsrc_fork(&mut *archive).unwrap(), b"resources");
+        for folder in [source, packed, unpacked] {
+            fs::remove_dir_all(folder).unwrap();
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn round_trips_through_extended_attributes() {
+        let (source, unpacked, packed) = (scratch("xattr-source"), scratch("xattr-unpacked"), scratch("xattr-packed"));
+        if !crate::testing::supports_xattrs(&source) {
+            for folder in [source, unpacked, packed] {
+                fs::remove_dir_all(folder).unwrap();
+            }
+            return;
+        }
+        let mut finder_info = [0; 32];
+        finder_info[..8].copy_from_slice(b"APPLtest");
+        let header = apple_double(&[
+            (EntryType::FinderInfo, &finder_info),
+            (EntryType::ResourceFork, b"resources"),
+        ]);
+        let data = io::Cursor::new(b"code".to_vec());
+        let mut archive = appledouble::parse_seekable(io::Cursor::new(header), data).unwrap();
+        applesingle::repack(&mut archive, File::create(source.join("App.as")).unwrap()).unwrap();
+
+        let representation = Representation::ExtendedAttributes;
+        let outcomes = unpack(&source, &unpacked, representation).unwrap();
+        let path = outcomes.into_iter().next().unwrap().result.unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"code");
+        assert_eq!(::xattr::get(&path, crate::xattr::RESOURCE_FORK).unwrap().unwrap(), b"resources");
+
+        let outcomes = pack(&unpacked, &packed, representation).unwrap();
+        let path = outcomes.into_iter().next().unwrap().result.unwrap();
+        let mut archive = crate::open(File::open(path).unwrap()).unwrap();
+        assert_eq!(archive.finder_info().unwrap().file_type, FileType::from(*b"APPL"));
+        assert_eq!(loose::read_rsrc_fork(&mut archive).unwrap(), b"resources");
+        for folder in [source, unpacked, packed] {
+            fs::remove_dir_all(folder).unwrap();
+        }
+    }
+}
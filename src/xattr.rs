@@ -0,0 +1,155 @@
+//! Files whose resource fork and Finder info are kept in extended
+//! attributes, following the convention of netatalk and Samba's `vfs_fruit`.
+//!
+//! Most file systems limit the size of an extended attribute, to a few
+//! kilobytes on ext4 and 64 KiB on others, so large resource forks may not
+//! fit.
+
+use std::{
+    io::{self, Seek, prelude::*},
+    path::Path,
+};
+
+use deku::prelude::*;
+
+use super::{
+    FinderInfo,
+    MultiForkArchive,
+    SeekableArchive,
+    applesingle,
+    error,
+    format::Format,
+    loose::{self, LooseFile},
+};
+
+/// The attribute holding the resource fork.
+pub const RESOURCE_FORK: &str = "user.com.apple.ResourceFork";
+/// The attribute holding the 32 bytes of Finder info and extended Finder
+/// info.
+pub const FINDER_INFO: &str = "user.com.apple.FinderInfo";
+
+/// The length of the Finder info attribute.
+const FINDER_INFO_LEN: usize = 32;
+
+/// Reads an extended attribute, treating an empty one as missing.
+fn get<P: AsRef<Path>>(path: P, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let value = ::xattr::get(path, name)?;
+    Ok(value.filter(|value| !value.is_empty()))
+}
+
+/// Opens the file at `path` along with the resource fork and Finder info in
+/// its extended attributes.
+pub fn open<P: AsRef<Path>>(path: P) -> error::Result<SeekableArchive<LooseFile>> {
+    let path = path.as_ref();
+    let rsrc = get(path, RESOURCE_FORK)?.unwrap_or_default();
+    let finder_info = get(path, FINDER_INFO)?;
+    loose::assemble(path, rsrc, finder_info, Format::ExtendedAttributes)
+}
+
+/// Stores `finder_info` in the extended attributes of the file at `path`,
+/// keeping the extended Finder info already there.
+pub fn set_finder_info<P: AsRef<Path>>(path: P, finder_info: &FinderInfo) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut info = get(path, FINDER_INFO)?.unwrap_or_default();
+    info.resize(FINDER_INFO_LEN, 0);
+    info.splice(..16, finder_info.to_bytes()?);
+    ::xattr::set(path, FINDER_INFO, &info)
+}
+
+/// Writes the data fork of `archive` to a file at `path`, its resource fork
+/// and Finder info to the file's extended attributes, and its modification
+/// date to the file's metadata.
+pub fn extract<A: MultiForkArchive + ?Sized, P: AsRef<Path>>(
+    archive: &mut A,
+    path: P,
+) -> error::Result<()> {
+    let path = path.as_ref();
+    loose::extract(archive, path, |archive, rsrc| {
+        if !rsrc.is_empty() {
+            ::xattr::set(path, RESOURCE_FORK, &rsrc)?;
+        }
+        if let Some(info) = applesingle::finder_info_bytes(archive)? {
+            ::xattr::set(path, FINDER_INFO, &info)?;
+        }
+        Ok(())
+    })
+}
+
+/// Packs the file at `path` and its extended attributes into an AppleSingle
+/// file.
+pub fn pack<P: AsRef<Path>, W: Write + Seek>(path: P, out: W) -> error::Result<W> {
+    applesingle::repack(&mut open(path)?, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor};
+
+    use super::*;
+    use crate::{Entry, FileType, testing::{scratch, supports_xattrs}};
+
+    #[test]
+    fn round_trips_through_attributes() {
+        let root = scratch("xattr-trip");
+        let path = root.join("App");
+        fs::write(&path, b"").unwrap();
+        if !supports_xattrs(&path) {
+            fs::remove_dir_all(root).unwrap();
+            return;
+        }
+        fs::remove_file(&path).unwrap();
+
+        let mut finf = [0; 16];
+        finf[..8].copy_from_slice(b"APPLtest");
+        let finf = FinderInfo::from_bytes((&finf, 0)).unwrap().1;
+        let mut builder = SeekableArchive::builder(Cursor::new(b"dataresources".to_vec()));
+        builder.archive().format(Format::AppleSingle);
+        builder.archive().finf(finf);
+        builder.data_fork(Entry { id: 1, offset: 0, len: 4 });
+        builder.rsrc_fork(Entry { id: 2, offset: 4, len: 9 });
+        extract(&mut builder.build().unwrap(), &path).unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"data");
+        assert_eq!(::xattr::get(&path, RESOURCE_FORK).unwrap().unwrap(), b"resources");
+        let info = ::xattr::get(&path, FINDER_INFO).unwrap().unwrap();
+        assert_eq!(info.len(), FINDER_INFO_LEN);
+        assert_eq!(&info[..8], b"APPLtest");
+
+        let mut archive = open(&path).unwrap();
+        assert_eq!(archive.format(), Format::ExtendedAttributes);
+        assert_eq!(archive.name().unwrap().as_bytes(), b"App");
+        assert_eq!(archive.finder_info(), Some(finf));
+        assert_eq!(loose::read_rsrc_fork(&mut archive).unwrap(), b"resources");
+
+        let mut packed = applesingle::parse_seekable(pack(&path, Cursor::new(vec![])).unwrap()).unwrap();
+        assert_eq!(packed.finder_info(), Some(finf));
+        assert_eq!(loose::read_rsrc_fork(&mut packed).unwrap(), b"resources");
+        let mut data = vec![];
+        packed.data_fork().unwrap().unwrap().read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"data");
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn keeps_extended_finder_info() {
+        let root = scratch("xattr-finf");
+        let path = root.join("Doc");
+        fs::write(&path, b"").unwrap();
+        if !supports_xattrs(&path) {
+            fs::remove_dir_all(root).unwrap();
+            return;
+        }
+        let mut info = vec![0; 16];
+        info.extend([0xaa; 16]);
+        ::xattr::set(&path, FINDER_INFO, &info).unwrap();
+
+        let mut finf = FinderInfo::from_bytes((&[0; 16], 0)).unwrap().1;
+        finf.file_type = FileType::from(*b"TEXT");
+        set_finder_info(&path, &finf).unwrap();
+        let info = ::xattr::get(&path, FINDER_INFO).unwrap().unwrap();
+        assert_eq!(&info[..4], b"TEXT");
+        assert_eq!(info[16..], [0xaa; 16]);
+        assert_eq!(open(&path).unwrap().finder_info(), Some(finf));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
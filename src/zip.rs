@@ -0,0 +1,336 @@
+//! Zip archives as the macOS Finder makes them, where the resource fork and
+//! Finder info of each file are kept in an AppleDouble header file under
+//! `__MACOSX/`, named as [`appledouble::Layout::DotUnderscore`] would name
+//! it.
+//!
+//! [`appledouble::Layout::DotUnderscore`]: crate::appledouble::Layout::DotUnderscore
+
+use std::{
+    collections::HashMap,
+    io::{
+        self,
+        Cursor,
+        Seek,
+        prelude::*,
+    },
+};
+
+use ::zip::{
+    CompressionMethod,
+    DateTime,
+    write::SimpleFileOptions,
+};
+use time::OffsetDateTime;
+
+use super::{
+    Date,
+    Dates,
+    Entry,
+    MultiForkArchive,
+    SeekableArchive,
+    appledouble::{AppleDoubleFile, AppleDoubleWriter},
+    applesingle::{self, EntryType},
+    error::{self, Error},
+    format::Format,
+    loose,
+};
+
+/// The folder holding the AppleDouble header files.
+pub const MACOSX: &str = "__MACOSX";
+const MACOSX_PREFIX: &str = "__MACOSX/";
+
+/// A file's AppleDouble header file followed by its data fork, both read
+/// into memory.
+pub type ZipFile = AppleDoubleFile<Cursor<Vec<u8>>, Cursor<Vec<u8>>>;
+
+/// Finds the name of the header file for the file named `name` within a zip
+/// archive, so that `folder/file` is described by `__MACOSX/folder/._file`.
+pub fn header_name(name: &str) -> Option<String> {
+    let name = name.strip_suffix('/').unwrap_or(name);
+    let (folder, file) = match name.rsplit_once('/') {
+        Some((folder, file)) => (Some(folder), file),
+        None => (None, name),
+    };
+    if file.is_empty() {
+        return None;
+    }
+    Some(match folder {
+        Some(folder) => format!("{MACOSX}/{folder}/._{file}"),
+        None => format!("{MACOSX}/._{file}"),
+    })
+}
+
+fn zip_error(e: ::zip::result::ZipError) -> Error {
+    io::Error::from(e).into()
+}
+
+/// A file within a zip archive and the header file which accompanies it.
+#[derive(Debug)]
+struct Member {
+    name: String,
+    data: usize,
+    header: Option<usize>,
+}
+
+/// Reads the files in a zip archive, merging each with its header file.
+pub struct ZipReader<R> {
+    zip: ::zip::ZipArchive<R>,
+    members: Vec<Member>,
+}
+
+impl <R: Read + Seek> ZipReader<R> {
+    /// Reads the central directory of a zip archive and pairs up its files
+    /// with their header files. Folders, and header files without a file,
+    /// are left out.
+    pub fn new(reader: R) -> error::Result<Self> {
+        let zip = ::zip::ZipArchive::new(reader).map_err(zip_error)?;
+        let names: Vec<(usize, &str)> = (0..zip.len())
+            .filter_map(|index| Some((index, zip.name_for_index(index)?)))
+            .collect();
+        let headers: HashMap<&str, usize> = names.iter()
+            .filter(|(_, name)| name.starts_with(MACOSX_PREFIX))
+            .map(|&(index, name)| (name, index))
+            .collect();
+        let members = names.iter()
+            .filter(|(_, name)| !name.ends_with('/') && !name.starts_with(MACOSX_PREFIX))
+            .map(|&(data, name)| Member {
+                name: name.to_string(),
+                data,
+                header: header_name(name)
+                    .and_then(|header| headers.get(header.as_str()).copied()),
+            })
+            .collect();
+        Ok(Self { zip, members })
+    }
+    /// The number of files, not counting folders or header files.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+    /// Whether there are no files, not counting folders or header files.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+    /// The full names of the files, in the order they are stored.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.members.iter().map(|member| member.name.as_str())
+    }
+    /// Reads the file with the given full name, if there is one.
+    pub fn by_name(&mut self, name: &str) -> error::Result<Option<SeekableArchive<ZipFile>>> {
+        match self.members.iter().position(|member| member.name == name) {
+            Some(index) => self.get(index).map(Some),
+            None => Ok(None),
+        }
+    }
+    /// Reads the `index`th file and its header file, merging them into a
+    /// single archive. The name and modification date come from the zip
+    /// archive unless the header file says otherwise.
+    pub fn get(&mut self, index: usize) -> error::Result<SeekableArchive<ZipFile>> {
+        let Member { name, data, header } = self.members.get(index)
+            .ok_or(Error::Incomplete)?;
+        let (data, modified) = {
+            let mut file = self.zip.by_index(*data).map_err(zip_error)?;
+            let mut bytes = vec![];
+            file.read_to_end(&mut bytes)?;
+            (bytes, file.last_modified())
+        };
+        let header = match header {
+            Some(header) => {
+                let mut file = self.zip.by_index(*header).map_err(zip_error)?;
+                let mut bytes = vec![];
+                file.read_to_end(&mut bytes)?;
+                bytes
+            },
+            None => vec![],
+        };
+        merge(name, header, data, modified)
+    }
+    /// Reads every file in turn.
+    pub fn archives(&mut self) -> impl Iterator<Item = error::Result<SeekableArchive<ZipFile>>> + '_ {
+        (0..self.len()).map(|index| self.get(index))
+    }
+    /// Gives back the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.zip.into_inner()
+    }
+}
+
+fn merge(
+    name: &str,
+    header: Vec<u8>,
+    data: Vec<u8>,
+    modified: Option<DateTime>,
+) -> error::Result<SeekableArchive<ZipFile>> {
+    let data_fork = Entry {
+        id: EntryType::DataFork.into(),
+        offset: header.len() as u64,
+        len: data.len() as u64,
+    };
+    let has_header = !header.is_empty();
+    let file = AppleDoubleFile::new(Cursor::new(header), Cursor::new(data))?;
+    let mut builder = SeekableArchive::builder(file);
+    let archive = builder.archive();
+    archive.format(Format::Zip);
+    if let Some(file_name) = name.rsplit('/').next() {
+        archive.name(loose::mac_name(file_name));
+    }
+    if let Some(modified) = modified.and_then(|modified| OffsetDateTime::try_from(modified).ok()) {
+        archive.date(Dates {
+            create: Date::UNKNOWN,
+            modify: Date::from_system_time(modified.into()),
+            backup: Date::UNKNOWN,
+            access: Date::UNKNOWN,
+        });
+    }
+    if has_header {
+        applesingle::parse_header_entries(&mut builder, Format::Zip, data_fork.offset)?;
+    }
+    builder.data_fork(data_fork);
+    builder.table_entry(data_fork);
+    builder.build()
+        .ok_or(Error::Incomplete)
+}
+
+/// Writes files into a zip archive, keeping their resource forks and Finder
+/// info in header files under `__MACOSX/` as the macOS Finder does.
+pub struct ZipWriter<W: Write + Seek> {
+    zip: ::zip::ZipWriter<W>,
+}
+
+impl <W: Write + Seek> ZipWriter<W> {
+    /// Starts an empty zip archive, which is not complete until
+    /// [`finish`](Self::finish) is called.
+    pub fn new(out: W) -> Self {
+        Self {
+            zip: ::zip::ZipWriter::new(out),
+        }
+    }
+    /// Adds the data fork of `archive` as the file named `name`, with a
+    /// header file if it has a resource fork or Finder info.
+    pub fn add<A: MultiForkArchive + ?Sized>(
+        &mut self,
+        name: &str,
+        archive: &mut A,
+    ) -> error::Result<()> {
+        let header_name = header_name(name)
+            .ok_or(Error::Incomplete)?;
+        let mut options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated);
+        let modified = archive.dates()
+            .and_then(|dates| dates.modify.to_system_time())
+            .and_then(|modified| DateTime::try_from(OffsetDateTime::from(modified)).ok());
+        if let Some(modified) = modified {
+            options = options.last_modified_time(modified);
+        }
+
+        self.zip.start_file(name, options).map_err(zip_error)?;
+        loose::write_forks(archive, &mut self.zip, |zip, archive, rsrc| {
+            let finder_info = applesingle::finder_info_bytes(archive)?;
+            if rsrc.is_empty() && finder_info.is_none() {
+                return Ok(());
+            }
+            let mut writer = AppleDoubleWriter::with_finder_info(archive, finder_info)?;
+            if !rsrc.is_empty() {
+                let len = rsrc.len() as u64;
+                writer.rsrc_fork(Cursor::new(rsrc), Some(len));
+            }
+            let mut header = Cursor::new(vec![]);
+            writer.write_seekable(&mut header, io::sink())?;
+            zip.start_file(header_name, options).map_err(zip_error)?;
+            zip.write_all(header.get_ref())?;
+            Ok(())
+        })
+    }
+    /// Finishes the zip archive by writing its central directory.
+    pub fn finish(self) -> error::Result<W> {
+        self.zip.finish().map_err(zip_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::{FileType, applesingle::EntryType, testing::apple_double};
+
+    fn finder_info(file_type: &[u8; 4]) -> [u8; 32] {
+        let mut finder_info = [0; 32];
+        finder_info[..4].copy_from_slice(file_type);
+        finder_info
+    }
+
+    fn read_data_fork<A: MultiForkArchive + ?Sized>(archive: &mut A) -> Vec<u8> {
+        let mut data = vec![];
+        archive.data_fork().unwrap().unwrap().read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn names_header_files() {
+        assert_eq!(header_name("file").unwrap(), "__MACOSX/._file");
+        assert_eq!(header_name("a/b/file").unwrap(), "__MACOSX/a/b/._file");
+        assert_eq!(header_name("a/folder/").unwrap(), "__MACOSX/a/._folder");
+        assert_eq!(header_name(""), None);
+        assert_eq!(header_name("a//"), None);
+    }
+
+    #[test]
+    fn merges_header_file() {
+        let modified = DateTime::from_date_and_time(2001, 9, 9, 1, 46, 40).unwrap();
+        let finf = finder_info(b"TEXT");
+        let mut archive = merge("docs/notes.txt", apple_double(&[(EntryType::FinderInfo, &finf)]), b"data".to_vec(), Some(modified)).unwrap();
+        assert_eq!(archive.format(), Format::Zip);
+        assert_eq!(archive.name().unwrap().as_bytes(), b"notes.txt");
+        let modify = archive.dates().unwrap().modify.to_system_time();
+        assert_eq!(modify, Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000)));
+        assert_eq!(archive.finder_info().unwrap().file_type, FileType::from(*b"TEXT"));
+        assert!(archive.rsrc_fork().unwrap().is_none());
+        assert_eq!(read_data_fork(&mut archive), b"data");
+
+        // the header file's own name wins over the zip archive's.
+        let header = apple_double(&[(EntryType::RealName, b"Notes"), (EntryType::ResourceFork, b"rsrc")]);
+        let mut archive = merge("notes.txt", header, b"data".to_vec(), None).unwrap();
+        assert_eq!(archive.name().unwrap().as_bytes(), b"Notes");
+        assert_eq!(archive.dates(), None);
+        assert_eq!(loose::read_rsrc_fork(&mut archive).unwrap(), b"rsrc");
+        assert_eq!(read_data_fork(&mut archive), b"data");
+
+        let mut archive = merge("plain", vec![], b"data".to_vec(), None).unwrap();
+        assert_eq!(archive.finder_info(), None);
+        assert_eq!(read_data_fork(&mut archive), b"data");
+    }
+
+    #[test]
+    fn round_trips_through_reader() {
+        let finf = finder_info(b"APPL");
+        let source = apple_double(&[
+            (EntryType::RealName, b"App"),
+            (EntryType::FinderInfo, &finf),
+            (EntryType::ResourceFork, b"resources"),
+        ]);
+        let mut app = merge("App", source, b"code".to_vec(), None).unwrap();
+        let mut plain = merge("plain.txt", vec![], b"text".to_vec(), None).unwrap();
+
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+        writer.add("folder/App", &mut app).unwrap();
+        writer.add("plain.txt", &mut plain).unwrap();
+        let zip = writer.finish().unwrap();
+
+        let raw = ::zip::ZipArchive::new(Cursor::new(zip.get_ref().clone())).unwrap();
+        let names: Vec<&str> = raw.file_names().collect();
+        assert_eq!(names.len(), 3);
+        assert!(names.contains(&"__MACOSX/folder/._App"));
+
+        let mut reader = ZipReader::new(zip).unwrap();
+        assert_eq!(reader.names().collect::<Vec<_>>(), ["folder/App", "plain.txt"]);
+        let mut app = reader.by_name("folder/App").unwrap().unwrap();
+        assert_eq!(app.name().unwrap().as_bytes(), b"App");
+        assert_eq!(app.finder_info().unwrap().file_type, FileType::from(*b"APPL"));
+        assert_eq!(loose::read_rsrc_fork(&mut app).unwrap(), b"resources");
+        assert_eq!(read_data_fork(&mut app), b"code");
+        let mut plain = reader.by_name("plain.txt").unwrap().unwrap();
+        assert_eq!(plain.finder_info(), None);
+        assert_eq!(read_data_fork(&mut plain), b"text");
+        assert!(reader.by_name("__MACOSX/folder/._App").unwrap().is_none());
+    }
+}